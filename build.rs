@@ -15,4 +15,19 @@ fn main() {
         hello_one, average_numbers, fib
     );
     fs::write(dest_path, data).unwrap();
+
+    // the files are included as they are, messages can have any quotes in them
+    let locales = ["en", "es"]
+        .map(|name| {
+            format!(
+                "(\"{}\", include_str!(concat!(env!(\"CARGO_MANIFEST_DIR\"), \"/locales/{}.json\")))",
+                name, name
+            )
+        })
+        .join(", ");
+    // slint only asks to rebuild when the ui changes, so the messages have to ask too
+    println!("cargo:rerun-if-changed=locales");
+    let dest_path = format!("{}/locales.rs", out_dir);
+    let data = format!("pub const LOCALES: [(&str, &str); 2] = [{}];", locales);
+    fs::write(dest_path, data).unwrap();
 }
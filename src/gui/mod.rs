@@ -13,17 +13,26 @@ use crate::lang::tokens::Type;
 use blockdata::{Assign, Block, BlockType, World, WorldManipulation};
 use data2gui::create_blockdata_from_world;
 use popup_asker::{Message, ask_popup};
-use slint::{Color, ComponentHandle, Model, ToSharedString, format, invoke_from_event_loop};
+use slint::{Color, ComponentHandle, Model, ToSharedString, invoke_from_event_loop};
 
 mod blockdata;
 mod data2gui;
 mod popup_asker;
 
-use crate::tester::{LEVELS, TestInfo, test_against_json};
+use crate::tester::{LEVELS, TestInfo, in_keywords, test_against_json};
 
-use super::lang::run_code;
+use super::lang::{
+    DEFAULT_SEED,
+    errors::render_errors,
+    explain::{fill, message_template},
+    keywords::KeywordSet,
+    run_code,
+};
 slint::include_modules!();
-pub fn run_gui_test(args: Vec<String>) -> Result<(), slint::PlatformError> {
+pub fn run_gui_test(
+    args: Vec<String>,
+    keywords: &'static KeywordSet,
+) -> Result<(), slint::PlatformError> {
     // main func for gui tests
     println!("gui stuff");
     let main_window = MainWindow::new()?;
@@ -53,9 +62,11 @@ pub fn run_gui_test(args: Vec<String>) -> Result<(), slint::PlatformError> {
     main_window.on_run_freestyle_code(move |code| {
         let main_window_weak = main_window_weak.clone();
         thread::spawn(move || {
-            let expensive_string = match run_code(code.try_into().unwrap()) {
-                Ok(int) => int.to_string(),
-                Err(e) => format!("{:#?}", e).to_string(),
+            let expensive_string = match run_code(code.to_string(), keywords, DEFAULT_SEED) {
+                Ok((int, warnings)) if warnings.is_empty() => int.to_string(),
+                // warnings go under the answer, the code still ran
+                Ok((int, warnings)) => format!("{}\n\n{}", int, render_errors(&warnings, &code)),
+                Err(e) => render_errors(&e, &code),
             };
             let main_window_clone = main_window_weak.clone();
             invoke_from_event_loop(move || {
@@ -71,29 +82,34 @@ pub fn run_gui_test(args: Vec<String>) -> Result<(), slint::PlatformError> {
         let main_window_weak = main_window_weak.clone();
         thread::spawn(move || {
             let info = TestInfo {
-                code: code.to_string() + " fun test() -> int { return helloOne(); }",
+                code: code.to_string()
+                    + &in_keywords(" fun test() -> int { return helloOne(); }", keywords),
                 inputs_type: vec![],
                 output_type: crate::lang::tokens::Type::Int,
+                keywords,
                 json: json::parse(LEVELS[0]).unwrap(),
             };
             let result = test_against_json(info);
             let mut final_string = String::new();
             if result.success {
-                final_string += "You Won!\n";
+                final_string += message_template("you_won");
+                final_string += "\n";
             } else {
-                final_string += "You LOSE! LOL!\n";
+                final_string += message_template("you_lost");
+                final_string += "\n";
             }
             if result.errors.len() > 0 {
-                final_string += "Unfortunately, you had some compile errors.\n";
-                final_string = format!("{}{:#?}", final_string, result.errors).to_string();
+                final_string += message_template("had_compile_errors");
+                final_string += "\n";
+                final_string += &render_errors(&result.errors, &result.code);
                 final_string += "\n";
             }
-            final_string.extend(
-                format!(
-                    "Correct: {} |#| Incorrect: {}",
-                    result.correct, result.incorrect
-                )
-                .chars(),
+            final_string += &fill(
+                message_template("score"),
+                &[
+                    ("correct", result.correct.to_string()),
+                    ("incorrect", result.incorrect.to_string()),
+                ],
             );
             let main_window_weak = main_window_weak.clone();
             invoke_from_event_loop(move || {
@@ -109,29 +125,34 @@ pub fn run_gui_test(args: Vec<String>) -> Result<(), slint::PlatformError> {
         let main_window_weak = main_window_weak.clone();
         thread::spawn(move || {
             let info = TestInfo {
-                code: code.to_string() + " fun test(dcml num1, dcml num2) -> dcml { return averageDecimals(num1, num2); }",
+                code: code.to_string()
+                    + &in_keywords(" fun test(dcml num1, dcml num2) -> dcml { return averageDecimals(num1, num2); }", keywords),
                 inputs_type: vec![Type::Dcml, Type::Dcml],
                 output_type: Type::Dcml,
+                keywords,
                 json: json::parse(LEVELS[1]).unwrap(),
             };
             let result = test_against_json(info);
             let mut final_string = String::new();
             if result.success {
-                final_string += "You Won!\n";
+                final_string += message_template("you_won");
+                final_string += "\n";
             } else {
-                final_string += "You LOSE! LOL!\n";
+                final_string += message_template("you_lost");
+                final_string += "\n";
             }
             if result.errors.len() > 0 {
-                final_string += "Unfortunately, you had some compile errors.\n";
-                final_string = format!("{}{:#?}", final_string, result.errors).to_string();
+                final_string += message_template("had_compile_errors");
+                final_string += "\n";
+                final_string += &render_errors(&result.errors, &result.code);
                 final_string += "\n";
             }
-            final_string.extend(
-                format!(
-                    "Correct: {} |#| Incorrect: {}",
-                    result.correct, result.incorrect
-                )
-                .chars(),
+            final_string += &fill(
+                message_template("score"),
+                &[
+                    ("correct", result.correct.to_string()),
+                    ("incorrect", result.incorrect.to_string()),
+                ],
             );
             let main_window_weak = main_window_weak.clone();
             invoke_from_event_loop(move || {
@@ -147,29 +168,34 @@ pub fn run_gui_test(args: Vec<String>) -> Result<(), slint::PlatformError> {
         let main_window_weak = main_window_weak.clone();
         thread::spawn(move || {
             let info = TestInfo {
-                code: code.to_string() + " fun test(int num) -> int { return fib(num); }",
+                code: code.to_string()
+                    + &in_keywords(" fun test(int num) -> int { return fib(num); }", keywords),
                 inputs_type: vec![],
                 output_type: crate::lang::tokens::Type::Int,
+                keywords,
                 json: json::parse(LEVELS[2]).unwrap(),
             };
             let result = test_against_json(info);
             let mut final_string = String::new();
             if result.success {
-                final_string += "You Won!\n";
+                final_string += message_template("you_won");
+                final_string += "\n";
             } else {
-                final_string += "You LOSE! LOL!\n";
+                final_string += message_template("you_lost");
+                final_string += "\n";
             }
             if result.errors.len() > 0 {
-                final_string += "Unfortunately, you had some compile errors.\n";
-                final_string = format!("{}{:#?}", final_string, result.errors).to_string();
+                final_string += message_template("had_compile_errors");
+                final_string += "\n";
+                final_string += &render_errors(&result.errors, &result.code);
                 final_string += "\n";
             }
-            final_string.extend(
-                format!(
-                    "Correct: {} |#| Incorrect: {}",
-                    result.correct, result.incorrect
-                )
-                .chars(),
+            final_string += &fill(
+                message_template("score"),
+                &[
+                    ("correct", result.correct.to_string()),
+                    ("incorrect", result.incorrect.to_string()),
+                ],
             );
             let main_window_weak = main_window_weak.clone();
             invoke_from_event_loop(move || {
@@ -191,9 +217,7 @@ pub fn run_gui_test(args: Vec<String>) -> Result<(), slint::PlatformError> {
                 ask_popup(
                     Message {
                         message_type: MessageType::ExprExpr,
-                        message_contents: String::from(
-                            "Please put in expression for the expression block.",
-                        ),
+                        message_contents: message_template("ask_expression").to_owned(),
                     },
                     &main_window_weak,
                 );
@@ -31,6 +31,14 @@ const ARRPOP_NUM: u8 = 26;
 const ARRPUSH_NUM: u8 = 27;
 const FREEARR_NUM: u8 = 28;
 const ARRIND_NUM: u8 = 29;
+const SHL_NUM: u8 = 30;
+const SHR_NUM: u8 = 31;
+const POW_NUM: u8 = 32;
+const CALLNATIVE_NUM: u8 = 33;
+const CALLHOST_NUM: u8 = 34;
+const NE_NUM: u8 = 35;
+const MOVPOP_NUM: u8 = 36;
+const JMPIFFALSE_NUM: u8 = 37;
 
 pub struct Assembler {
     code: Vec<Instruction>,
@@ -44,10 +52,7 @@ impl Assembler {
         let mut bc: Vec<u8> = Vec::new();
         for inst in no_labels {
             match inst {
-                NoLabelInst::Ret(x) => {
-                    bc.push(RET_NUM);
-                    bc.extend_from_slice(&x.to_le_bytes());
-                }
+                NoLabelInst::Ret => bc.push(RET_NUM),
                 NoLabelInst::Push(x, y) => {
                     bc.push(PUSH_NUM);
                     bc.push(x);
@@ -102,6 +107,27 @@ impl Assembler {
                 NoLabelInst::ArrPush => bc.push(ARRPUSH_NUM),
                 NoLabelInst::FreeArr => bc.push(FREEARR_NUM),
                 NoLabelInst::ArrInd => bc.push(ARRIND_NUM),
+                NoLabelInst::Shl => bc.push(SHL_NUM),
+                NoLabelInst::Shr => bc.push(SHR_NUM),
+                NoLabelInst::Pow => bc.push(POW_NUM),
+                NoLabelInst::CallNative(x) => {
+                    bc.push(CALLNATIVE_NUM);
+                    bc.extend_from_slice(&x.to_le_bytes());
+                }
+                NoLabelInst::CallHost(x) => {
+                    bc.push(CALLHOST_NUM);
+                    bc.extend_from_slice(&x.to_le_bytes());
+                }
+                NoLabelInst::Ne => bc.push(NE_NUM),
+                NoLabelInst::MovPop(x) => {
+                    bc.push(MOVPOP_NUM);
+                    bc.extend_from_slice(&x.to_le_bytes());
+                }
+                NoLabelInst::JmpIfFalse(cmp, x) => {
+                    bc.push(JMPIFFALSE_NUM);
+                    bc.push(cmp.to_num());
+                    bc.extend_from_slice(&x.to_le_bytes());
+                }
             }
         }
         bc
@@ -125,7 +151,7 @@ impl Assembler {
         }
         for inst in code {
             out.push(match inst {
-                Instruction::Ret(x) => NoLabelInst::Ret(*x),
+                Instruction::Ret => NoLabelInst::Ret,
                 Instruction::Push(x, y) => NoLabelInst::Push(*x, *y),
                 Instruction::Pop => NoLabelInst::Pop,
                 Instruction::Mov(x) => NoLabelInst::Mov(*x),
@@ -155,13 +181,23 @@ impl Assembler {
                 Instruction::ArrPush => NoLabelInst::ArrPush,
                 Instruction::FreeArr => NoLabelInst::FreeArr,
                 Instruction::ArrInd => NoLabelInst::ArrInd,
+                Instruction::Shl => NoLabelInst::Shl,
+                Instruction::Shr => NoLabelInst::Shr,
+                Instruction::Pow => NoLabelInst::Pow,
+                Instruction::CallNative(x) => NoLabelInst::CallNative(*x),
+                Instruction::CallHost(x) => NoLabelInst::CallHost(*x),
+                Instruction::Ne => NoLabelInst::Ne,
+                Instruction::MovPop(x) => NoLabelInst::MovPop(*x),
+                Instruction::JmpIfFalse(cmp, s) => {
+                    NoLabelInst::JmpIfFalse(*cmp, *map.get(s).expect("Invalid asm"))
+                }
             })
         }
         out
     }
 }
 pub enum NoLabelInst {
-    Ret(u16),
+    Ret,
     Push(u8, u16),
     Pop,
     Mov(u16),
@@ -190,12 +226,21 @@ pub enum NoLabelInst {
     ArrPush,
     FreeArr,
     ArrInd,
+    Shl,
+    Shr,
+    Pow,
+    CallNative(u16),
+    CallHost(u16),
+    Ne,
+    MovPop(u16),
+    JmpIfFalse(Cmp, u32),
 }
 
 //bytetext asm has labels
 #[derive(Debug)]
 pub enum Instruction {
-    Ret(u16),
+    Ret,
+    // where a var is in push and mov is counted from the start of the function's frame
     Push(u8, u16),
     Pop,
     Mov(u16),
@@ -225,11 +270,22 @@ pub enum Instruction {
     ArrPush,
     FreeArr,
     ArrInd,
+    Shl,
+    Shr,
+    Pow,
+    CallNative(u16),
+    CallHost(u16),
+    // these are only made by the peephole pass, out of the ones above
+    Ne,
+    // Mov then Pop
+    MovPop(u16),
+    // compares the top two values and jumps if that is false, like a comparison then Jnz
+    JmpIfFalse(Cmp, String),
 }
 impl Instruction {
     fn size(&self) -> u32 {
         match self {
-            Instruction::Ret(_) => 3,
+            Instruction::Ret => 1,
             Instruction::Push(_, _) => 4,
             Instruction::Pop => 1,
             Instruction::Mov(_) => 3,
@@ -259,6 +315,14 @@ impl Instruction {
             Instruction::ArrPush => 1,
             Instruction::FreeArr => 1,
             Instruction::ArrInd => 1,
+            Instruction::Shl => 1,
+            Instruction::Shr => 1,
+            Instruction::Pow => 1,
+            Instruction::CallNative(_) => 3,
+            Instruction::CallHost(_) => 3,
+            Instruction::Ne => 1,
+            Instruction::MovPop(_) => 3,
+            Instruction::JmpIfFalse(_, _) => 6,
         }
     }
 }
@@ -267,10 +331,7 @@ impl fmt::Display for Instruction {
         let mut ans = String::new();
         let mut others = Vec::new();
         ans += match self {
-            Instruction::Ret(x) => {
-                others.push(x.to_string());
-                "ret"
-            }
+            Instruction::Ret => "ret",
             Instruction::Push(x, y) => {
                 others.push(x.to_string());
                 others.push(y.to_string());
@@ -329,6 +390,7 @@ impl fmt::Display for Instruction {
                         Type::CallStack => "callstack",
                         Type::Array(_) => "array",
                         Type::Void => "VOID",
+                        Type::Unknown => "UNKNOWN",
                     }
                     .to_string(),
                 );
@@ -339,6 +401,27 @@ impl fmt::Display for Instruction {
             Instruction::ArrPush => "arrpush",
             Instruction::FreeArr => "freearr",
             Instruction::ArrInd => "arrind",
+            Instruction::Shl => "shl",
+            Instruction::Shr => "shr",
+            Instruction::Pow => "pow",
+            Instruction::CallNative(x) => {
+                others.push(x.to_string());
+                "callnative"
+            }
+            Instruction::CallHost(x) => {
+                others.push(x.to_string());
+                "callhost"
+            }
+            Instruction::Ne => "ne",
+            Instruction::MovPop(x) => {
+                others.push(x.to_string());
+                "movpop"
+            }
+            Instruction::JmpIfFalse(cmp, s) => {
+                others.push(cmp.to_string());
+                others.push(s.clone());
+                "jmpiffalse"
+            }
         };
         for thing in others {
             ans += " ";
@@ -348,11 +431,67 @@ impl fmt::Display for Instruction {
     }
 }
 
+/// The comparisons `JmpIfFalse` can do.
+#[derive(Debug, Clone, Copy)]
+pub enum Cmp {
+    Eq,
+    Ne,
+    L,
+    Le,
+    G,
+    Ge,
+}
+impl Cmp {
+    /// The comparison an instruction number stands for, if it is one.
+    pub fn from_num(num: u8) -> Option<Cmp> {
+        Some(match num {
+            EQ_NUM => Cmp::Eq,
+            NE_NUM => Cmp::Ne,
+            L_NUM => Cmp::L,
+            LE_NUM => Cmp::Le,
+            G_NUM => Cmp::G,
+            GE_NUM => Cmp::Ge,
+            _ => return None,
+        })
+    }
+    // the same number as the instruction that does just the comparison
+    fn to_num(self) -> u8 {
+        match self {
+            Cmp::Eq => EQ_NUM,
+            Cmp::Ne => NE_NUM,
+            Cmp::L => L_NUM,
+            Cmp::Le => LE_NUM,
+            Cmp::G => G_NUM,
+            Cmp::Ge => GE_NUM,
+        }
+    }
+}
+impl fmt::Display for Cmp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Cmp::Eq => "eq",
+            Cmp::Ne => "ne",
+            Cmp::L => "l",
+            Cmp::Le => "le",
+            Cmp::G => "g",
+            Cmp::Ge => "ge",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 pub fn print_instructions(inst_vec: &Vec<Instruction>) {
     println!("INSTRUCTIONS:\nBYTE INDEX -- INSTRUCTION");
+    print!("{}", listing(inst_vec));
+}
+
+/// One line per instruction, with the byte index it will be assembled at.
+pub fn listing(inst_vec: &[Instruction]) -> String {
+    let mut out = String::new();
     let mut byte_index = 0;
     for inst in inst_vec.iter() {
-        println!("{} -- {}", byte_index, inst);
+        out += &format!("{} -- {}\n", byte_index, inst);
         byte_index += inst.size();
     }
+    out
 }
@@ -9,12 +9,24 @@ pub struct FunctionAst {
     pub ret_type: Type,
 }
 
+/// `const int SIZE = 10;` outside of any function.
+/// The value has to be something that can be worked out before running.
+#[derive(Clone, Debug)]
+pub struct GlobalConst {
+    pub typ: Type,
+    pub ident: String,
+    pub ident_loc: Loc,
+    pub val: ExprAST,
+    pub val_loc: Loc,
+}
+
 #[derive(Clone, Debug)]
 pub enum ExprAST {
-    Var(String),
+    // the loc is where the name was written, for error messages
+    Var(String, Loc),
     Lit(Literal),
     BinOp(Operator, Box<ExprAST>, Box<ExprAST>),
-    Call(String, Vec<Expression>),
+    Call(String, Vec<Expression>, Loc),
     Casted(Type, Box<ExprAST>),
     DotOp(DotOp, Box<ExprAST>),
     Indexed(Box<ExprAST>, Box<ExprAST>),
@@ -36,14 +48,32 @@ pub enum Statement {
     While(WhileBlock),
     Return(Return),
 }
+impl Statement {
+    // where the statement is, for pointing at a whole statement (ifs and whiles are just the keyword)
+    pub fn loc(&self) -> Loc {
+        match self {
+            Statement::Expr(expr) => expr.loc,
+            Statement::Decl(decl) => decl.loc,
+            Statement::Assign(assign) => Loc::span(assign.ident_loc, assign.val_loc),
+            Statement::If(ifblock) => ifblock.loc,
+            Statement::While(whileblock) => whileblock.loc,
+            Statement::Return(ret) => ret.loc,
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Declaration {
-    pub typ: Type,
+    // `const int x = 5;` can't be assigned to afterwards
+    pub constant: bool,
+    // None for `var x = ...;`, the type comes from the value
+    pub typ: Option<Type>,
     pub ident: String,
     pub ident_loc: Loc,
     pub val: ExprAST,
     pub val_loc: Loc,
+    // from the type, `var` or `const` up to the end of the value
+    pub loc: Loc,
 }
 
 #[derive(Clone, Debug)]
@@ -81,13 +111,32 @@ pub struct Return {
     pub loc: Loc,
 }
 
+/// Where something is in the code, from (`line`, `col`) up to just before (`end_line`, `end_col`).
+/// Lines and columns start at 1.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Loc {
     pub line: u32,
     pub col: u32,
+    pub end_line: u32,
+    pub end_col: u32,
 }
 impl Loc {
+    // a loc that is only one spot
     pub fn new(line: u32, col: u32) -> Self {
-        Loc { line, col }
+        Loc {
+            line,
+            col,
+            end_line: line,
+            end_col: col,
+        }
+    }
+    // from the start of `start` to the end of `end`
+    pub fn span(start: Loc, end: Loc) -> Self {
+        Loc {
+            line: start.line,
+            col: start.col,
+            end_line: end.end_line,
+            end_col: end.end_col,
+        }
     }
 }
@@ -0,0 +1,274 @@
+use super::{errors::ErrorType, tokens::Type, wrapped_val::WrappedVal};
+
+/// A function that is built into the language instead of being written in it.
+/// The same name can show up more than once with different parameter types,
+/// like `abs(int)` and `abs(dcml)`, and the id is what `CallNative` carries.
+pub struct Builtin {
+    pub name: &'static str,
+    pub params: &'static [Type],
+    pub ret_type: Type,
+}
+
+const ABS_INT: u16 = 0;
+const ABS_DCML: u16 = 1;
+const MIN_INT: u16 = 2;
+const MIN_DCML: u16 = 3;
+const MAX_INT: u16 = 4;
+const MAX_DCML: u16 = 5;
+const SQRT: u16 = 6;
+const POW_INT: u16 = 7;
+const POW_DCML: u16 = 8;
+const FLOOR: u16 = 9;
+const CEIL: u16 = 10;
+const ROUND: u16 = 11;
+const RANDOM: u16 = 12;
+
+// the index in this table is the id of the builtin
+pub const BUILTINS: [Builtin; 13] = [
+    Builtin {
+        name: "abs",
+        params: &[Type::Int],
+        ret_type: Type::Int,
+    },
+    Builtin {
+        name: "abs",
+        params: &[Type::Dcml],
+        ret_type: Type::Dcml,
+    },
+    Builtin {
+        name: "min",
+        params: &[Type::Int, Type::Int],
+        ret_type: Type::Int,
+    },
+    Builtin {
+        name: "min",
+        params: &[Type::Dcml, Type::Dcml],
+        ret_type: Type::Dcml,
+    },
+    Builtin {
+        name: "max",
+        params: &[Type::Int, Type::Int],
+        ret_type: Type::Int,
+    },
+    Builtin {
+        name: "max",
+        params: &[Type::Dcml, Type::Dcml],
+        ret_type: Type::Dcml,
+    },
+    Builtin {
+        name: "sqrt",
+        params: &[Type::Dcml],
+        ret_type: Type::Dcml,
+    },
+    Builtin {
+        name: "pow",
+        params: &[Type::Int, Type::Int],
+        ret_type: Type::Int,
+    },
+    Builtin {
+        name: "pow",
+        params: &[Type::Dcml, Type::Dcml],
+        ret_type: Type::Dcml,
+    },
+    // floor, ceil and round give back an int, since that is what you
+    // usually want after rounding
+    Builtin {
+        name: "floor",
+        params: &[Type::Dcml],
+        ret_type: Type::Int,
+    },
+    Builtin {
+        name: "ceil",
+        params: &[Type::Dcml],
+        ret_type: Type::Int,
+    },
+    Builtin {
+        name: "round",
+        params: &[Type::Dcml],
+        ret_type: Type::Int,
+    },
+    // random(low, high) includes both ends
+    Builtin {
+        name: "random",
+        params: &[Type::Int, Type::Int],
+        ret_type: Type::Int,
+    },
+];
+
+pub fn is_builtin(name: &str) -> bool {
+    BUILTINS.iter().any(|builtin| builtin.name == name)
+}
+
+/// Finds the id of the builtin with that name whose params match the arg types.
+pub fn find_builtin(name: &str, arg_types: &[Type]) -> Option<u16> {
+    BUILTINS
+        .iter()
+        .position(|builtin| builtin.name == name && builtin.params == arg_types)
+        .map(|ind| ind as u16)
+}
+
+/// All the signatures for a builtin name, used for error messages.
+pub fn builtin_signatures(name: &str) -> Vec<&'static [Type]> {
+    BUILTINS
+        .iter()
+        .filter(|builtin| builtin.name == name)
+        .map(|builtin| builtin.params)
+        .collect()
+}
+
+/// Runs the builtin with the args in the order they were written.
+/// Errors crash the program.
+pub fn call_builtin(
+    id: u16,
+    args: Vec<WrappedVal>,
+    rng: &mut Rng,
+) -> Result<WrappedVal, ErrorType> {
+    use WrappedVal::{Dcml, Int};
+    Ok(match (id, args.as_slice()) {
+        (ABS_INT, [Int(x)]) => match x.checked_abs() {
+            Some(ans) => Int(ans),
+            None => return Err(ErrorType::AbsOverflow { value: *x }),
+        },
+        (ABS_DCML, [Dcml(x)]) => Dcml(x.abs()),
+        (MIN_INT, [Int(x), Int(y)]) => Int(*x.min(y)),
+        (MIN_DCML, [Dcml(x), Dcml(y)]) => Dcml(x.min(*y)),
+        (MAX_INT, [Int(x), Int(y)]) => Int(*x.max(y)),
+        (MAX_DCML, [Dcml(x), Dcml(y)]) => Dcml(x.max(*y)),
+        (SQRT, [Dcml(x)]) => {
+            if *x < 0.0 {
+                return Err(ErrorType::NegativeSqrt { value: *x });
+            }
+            Dcml(x.sqrt())
+        }
+        (POW_INT | POW_DCML, _) => {
+            let mut args = args.into_iter();
+            let (Some(base), Some(exp)) = (args.next(), args.next()) else {
+                unreachable!();
+            };
+            return base.checked_pow(exp);
+        }
+        (FLOOR, [Dcml(x)]) => Int(rounded_to_int("floor", *x, x.floor())?),
+        (CEIL, [Dcml(x)]) => Int(rounded_to_int("ceil", *x, x.ceil())?),
+        (ROUND, [Dcml(x)]) => Int(rounded_to_int("round", *x, x.round())?),
+        (RANDOM, [Int(low), Int(high)]) => {
+            if low > high {
+                return Err(ErrorType::BadRandomRange {
+                    low: *low,
+                    high: *high,
+                });
+            }
+            let range = (*high as i64 - *low as i64 + 1) as u64;
+            Int((*low as i64 + rng.below(range) as i64) as i32)
+        }
+        _ => unreachable!(),
+    })
+}
+
+// `as i32` would quietly stop at the biggest int, and turn NaN into 0
+fn rounded_to_int(name: &str, value: f64, rounded: f64) -> Result<i32, ErrorType> {
+    if rounded.is_nan() || rounded < i32::MIN as f64 || rounded > i32::MAX as f64 {
+        return Err(ErrorType::NotAnInt {
+            name: name.to_owned(),
+            value,
+        });
+    }
+    Ok(rounded as i32)
+}
+
+/// Small xorshift generator, so the same seed always gives the same numbers.
+/// Level tests depend on that to be reproducible.
+pub struct Rng {
+    state: u64,
+}
+impl Rng {
+    pub const DEFAULT_SEED: u64 = 0x2545_F491_4F6C_DD1D;
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck on zero forever
+        Rng {
+            state: if seed == 0 { Rng::DEFAULT_SEED } else { seed },
+        }
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+    /// A number from 0 up to (not including) `range`, each one as likely as the others.
+    pub fn below(&mut self, range: u64) -> u64 {
+        // `% range` alone would pick the small numbers a bit more often, so
+        // numbers from the last, partial, run of `range` are thrown away
+        let limit = u64::MAX - u64::MAX % range;
+        loop {
+            let num = self.next_u64();
+            if num < limit {
+                return num % range;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rng;
+    use crate::lang::{
+        keywords::ENGLISH,
+        run_code,
+        test_util::{errors, run},
+    };
+
+    const CODE: &str = "fun main() -> int {
+    return random(1, 1000000) + random(1, 1000000);
+}";
+
+    #[test]
+    fn same_seed_same_numbers() {
+        let roll = |seed| run_code(CODE.to_owned(), &ENGLISH, seed).unwrap().0;
+        assert_eq!(roll(7), roll(7));
+        assert_ne!(roll(7), roll(8));
+    }
+
+    // the body of main
+    fn crash(body: &str) -> &'static str {
+        errors(&format!("fun main() -> int {{ {} }}", body))[0]
+            .e_type
+            .code()
+    }
+    fn value(body: &str) -> i32 {
+        run(&format!("fun main() -> int {{ {} }}", body))
+    }
+
+    #[test]
+    fn abs_of_the_smallest_int_crashes() {
+        assert_eq!(crash("return abs(0 - 2147483647 - 1);"), "R0009");
+        assert_eq!(value("return abs(0 - 2147483647);"), i32::MAX);
+    }
+
+    #[test]
+    fn rounding_has_to_fit_in_an_int() {
+        assert_eq!(crash("return floor(10000000000.0);"), "R0010");
+        assert_eq!(crash("return ceil(0.0 - 10000000000.0);"), "R0010");
+        assert_eq!(crash("return round(2147483647.5);"), "R0010");
+        assert_eq!(crash("dcml nan = 0.0 / 0.0; return floor(nan);"), "R0010");
+        assert_eq!(
+            value("return floor(2147483647.5) + ceil(0.0 - 2147483648.0);"),
+            -1
+        );
+        assert_eq!(
+            value("return round(2.5) * 100 + floor(0.0 - 1.5) * 10 + ceil(1.2);"),
+            282
+        );
+    }
+
+    #[test]
+    fn below_stays_in_range() {
+        let mut rng = Rng::new(3);
+        let mut seen = [0; 3];
+        for _ in 0..3000 {
+            seen[rng.below(3) as usize] += 1;
+        }
+        assert!(seen.iter().all(|&count| count > 900), "{:?}", seen);
+        // the biggest range random(low, high) can ask for
+        assert!(rng.below(1 << 32) < 1 << 32);
+    }
+}
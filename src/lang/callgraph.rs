@@ -0,0 +1,144 @@
+use std::collections::{HashMap, HashSet};
+
+use super::ast::{DotOp, ExprAST, FunctionAst, Statement};
+
+/// Which functions each function in the code calls. Builtins and host functions
+/// show up as callees, but only functions from the code are followed.
+pub struct CallGraph {
+    calls: HashMap<String, HashSet<String>>,
+}
+impl CallGraph {
+    pub fn new(ast: &[FunctionAst]) -> Self {
+        let calls = ast
+            .iter()
+            .map(|func| {
+                let mut called = HashSet::new();
+                collect_calls(&func.code, &mut called);
+                (func.name.to_owned(), called)
+            })
+            .collect();
+        CallGraph { calls }
+    }
+    /// Every function that can run once `name` is called, counting `name`.
+    /// Empty if there is no function called `name`.
+    pub fn reachable_from(&self, name: &str) -> HashSet<&str> {
+        let mut reached = HashSet::new();
+        let mut to_visit = Vec::new();
+        if let Some((name, _)) = self.calls.get_key_value(name) {
+            reached.insert(name.as_str());
+            to_visit.push(name.as_str());
+        }
+        while let Some(name) = to_visit.pop() {
+            for callee in &self.calls[name] {
+                // builtins and host functions aren't in the map
+                if let Some((callee, _)) = self.calls.get_key_value(callee)
+                    && reached.insert(callee.as_str())
+                {
+                    to_visit.push(callee.as_str());
+                }
+            }
+        }
+        reached
+    }
+    /// Whether `name` can end up calling itself, right away or through other functions.
+    pub fn is_recursive(&self, name: &str) -> bool {
+        let Some(callees) = self.calls.get(name) else {
+            return false;
+        };
+        callees
+            .iter()
+            .any(|callee| self.reachable_from(callee).contains(name))
+    }
+}
+
+fn collect_calls(block: &[Statement], called: &mut HashSet<String>) {
+    for statement in block {
+        match statement {
+            Statement::Expr(expr) => collect_expr_calls(&expr.expr, called),
+            Statement::Decl(decl) => collect_expr_calls(&decl.val, called),
+            Statement::Assign(assign) => collect_expr_calls(&assign.val, called),
+            Statement::If(ifblock) => {
+                collect_expr_calls(&ifblock.cond.expr, called);
+                collect_calls(&ifblock.tcode, called);
+                collect_calls(&ifblock.ecode, called);
+            }
+            Statement::While(whileblock) => {
+                collect_expr_calls(&whileblock.cond.expr, called);
+                collect_calls(&whileblock.code, called);
+            }
+            Statement::Return(ret) => collect_expr_calls(&ret.expr.expr, called),
+        }
+    }
+}
+
+fn collect_expr_calls(expr: &ExprAST, called: &mut HashSet<String>) {
+    match expr {
+        ExprAST::Var(_, _) | ExprAST::Lit(_) => (),
+        ExprAST::BinOp(_, lhs, rhs) | ExprAST::Indexed(lhs, rhs) => {
+            collect_expr_calls(lhs, called);
+            collect_expr_calls(rhs, called);
+        }
+        ExprAST::Call(name, args, _) => {
+            called.insert(name.to_owned());
+            for arg in args {
+                collect_expr_calls(&arg.expr, called);
+            }
+        }
+        ExprAST::Casted(_, expr) => collect_expr_calls(expr, called),
+        ExprAST::DotOp(op, expr) => {
+            collect_expr_calls(expr, called);
+            if let DotOp::Push(val) = op {
+                collect_expr_calls(val, called);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::lang::{DEFAULT_SEED, call_graph, keywords::ENGLISH, run_code};
+
+    const CODE: &str = "fun is_even(int n) -> bool {
+    if n == 0 { return true; }
+    return is_odd(n - 1);
+}
+fun is_odd(int n) -> bool {
+    if n == 0 { return false; }
+    return is_even(n - 1);
+}
+fun lonely(int n) -> int {
+    if n == 0 { return 0; }
+    return lonely(n - 1);
+}
+fun main() -> int {
+    if is_even(abs(10)) { return 1; }
+    return 0;
+}";
+
+    #[test]
+    fn mutual_recursion() {
+        let graph = call_graph(CODE.to_owned(), &ENGLISH).unwrap();
+        assert_eq!(
+            graph.reachable_from("main"),
+            HashSet::from(["main", "is_even", "is_odd"])
+        );
+        assert!(graph.is_recursive("is_even"));
+        assert!(graph.is_recursive("is_odd"));
+        assert!(graph.is_recursive("lonely"));
+        assert!(!graph.is_recursive("main"));
+        assert!(graph.reachable_from("missing").is_empty());
+    }
+
+    #[test]
+    fn unreached_functions_are_left_out() {
+        let (val, warnings) = run_code(CODE.to_owned(), &ENGLISH, DEFAULT_SEED).unwrap();
+        assert_eq!(val, 1);
+        let codes = warnings
+            .iter()
+            .map(|warning| warning.e_type.code())
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec!["W0003"]);
+    }
+}
@@ -6,7 +6,11 @@ use std::{
 
 use super::{
     asm::Instruction,
-    ast::{Assignment, DotOp, ExprAST, FunctionAst, Statement},
+    ast::{Assignment, DotOp, ExprAST, Expression, FunctionAst, GlobalConst, Statement},
+    builtins::{BUILTINS, find_builtin, is_builtin},
+    callgraph::CallGraph,
+    consteval::eval_const_expr,
+    host::HostRegistry,
     tokens::{Literal, Operator, Type},
     typecheck::TypeChecker,
     vm::get_type_size,
@@ -30,10 +34,13 @@ pub struct FuncCompiler {
     ret_types: Arc<HashMap<String, Type>>,
     // ret_types tells it how much mem to allocate to amount_in_stack
     // when a call is given.
+    host: Arc<HostRegistry>,
+    // global constants, already worked out
+    globals: Arc<HashMap<String, Literal>>,
     code: Vec<Instruction>,
     func: FunctionAst,
     var_tracker: HashMap<String, (u16, Type)>,
-    // SoF will always be zero
+    // how big the frame is so far. SoF will always be zero
     amount_in_stack: u16,
     scoped_vars: Vec<(u16, u16, u16)>,
 }
@@ -51,6 +58,8 @@ pub struct CompilerComposer {
     consts: Vec<u8>,
     pool: Vec<String>,
     funcs: Vec<FunctionAst>,
+    globals: HashMap<String, Literal>,
+    host: HostRegistry,
 }
 
 impl FuncCompiler {
@@ -59,12 +68,16 @@ impl FuncCompiler {
         consts: Arc<Vec<u8>>,
         pool: Arc<Vec<String>>,
         ret_types: Arc<HashMap<String, Type>>,
+        host: Arc<HostRegistry>,
+        globals: Arc<HashMap<String, Literal>>,
         func: FunctionAst,
     ) -> Self {
         FuncCompiler {
             consts,
             pool,
             ret_types,
+            host,
+            globals,
             code: Vec::new(),
             func,
             var_tracker: HashMap::new(),
@@ -104,7 +117,8 @@ impl FuncCompiler {
                             .try_into()
                             .unwrap(),
                     );
-                    if *find_dcml == dcml {
+                    // by bits, so a folded 0.0 / 0.0 finds its NaN and -0.0 isn't 0.0
+                    if find_dcml.to_bits() == dcml.to_bits() {
                         return Some(byte_ind as u16);
                     }
                     byte_ind += get_type_size(DCML_NUM);
@@ -162,30 +176,35 @@ impl FuncCompiler {
             Operator::And => Instruction::And,
             Operator::Or => Instruction::Or,
             Operator::Xor => Instruction::Xor,
+            Operator::Shl => Instruction::Shl,
+            Operator::Shr => Instruction::Shr,
+            Operator::Pow => Instruction::Pow,
         });
         if let Operator::NEq = op {
             self.code.push(Instruction::Not);
         }
     }
     fn track_var(&mut self, id: String, typ: Type) {
-        // doesn't increment amount_in_stack
-        let ind = self.amount_in_stack + typ.size() as u16;
-        self.amount_in_stack += typ.size() as u16;
+        // doesn't increment amount_in_stack, the value is already pushed
+        let ind = self.amount_in_stack;
         self.var_tracker.insert(id, (ind as u16, typ));
     }
-    // gets the offset from the top of the var ident passed in
+    // gets where the var ends in the frame, which stays the same while it is alive
     fn get_var(&self, id: &String) -> (u16, Type) {
-        let (varindex, vartype) = self.var_tracker.get(id).unwrap();
-        (self.amount_in_stack as u16 - varindex, vartype.to_owned())
+        self.var_tracker.get(id).unwrap().to_owned()
     }
     // recursively compiles the ExprAST and pushes it to self.code
     fn compile_expr(&mut self, expr: ExprAST) -> Type {
         match expr {
-            ExprAST::Var(x) => {
-                let (varoffset, vartype) = self.get_var(&x);
-                const PUSH_FROM_STACK: u8 = 0;
-                self.code
-                    .push(Instruction::Push(PUSH_FROM_STACK, varoffset));
+            ExprAST::Var(x, _) if !self.var_tracker.contains_key(&x) => {
+                // a global constant, which is pushed just like a literal
+                let lit = self.globals.get(&x).unwrap().to_owned();
+                self.compile_expr(ExprAST::Lit(lit))
+            }
+            ExprAST::Var(x, _) => {
+                let (varindex, vartype) = self.get_var(&x);
+                const PUSH_FROM_FRAME: u8 = 0;
+                self.code.push(Instruction::Push(PUSH_FROM_FRAME, varindex));
                 self.amount_in_stack += vartype.size() as u16;
                 return vartype;
             } // find type of the var, and add memsize to stack
@@ -211,12 +230,33 @@ impl FuncCompiler {
                 self.compile_op(op);
                 return endtype;
             }
-            ExprAST::Call(s, x) => {
+            ExprAST::Call(s, x, _) if is_builtin(&s) => {
+                let amount_in_stack_before = self.amount_in_stack;
+                let mut arg_types = Vec::new();
+                for expr in x {
+                    arg_types.push(self.compile_expr(expr.expr));
+                }
+                self.amount_in_stack = amount_in_stack_before;
+                let id = find_builtin(&s, &arg_types).unwrap();
+                let datatype = BUILTINS[id as usize].ret_type.to_owned();
+                self.amount_in_stack += datatype.size() as u16;
+                self.code.push(Instruction::CallNative(id));
+                return datatype;
+            }
+            ExprAST::Call(s, x, _) => {
                 let amount_in_stack_before = self.amount_in_stack;
                 for expr in x {
                     self.compile_expr(expr.expr);
                 }
                 self.amount_in_stack = amount_in_stack_before;
+                if let Some((id, host_func)) = self.host.find(&s) {
+                    let datatype = host_func.ret_type.to_owned();
+                    if datatype != Type::Void {
+                        self.amount_in_stack += datatype.size() as u16;
+                    }
+                    self.code.push(Instruction::CallHost(id));
+                    return datatype;
+                }
                 let datatype = self.ret_types.get(&s).unwrap();
                 self.amount_in_stack += datatype.size() as u16;
                 self.code.push(Instruction::Call(s));
@@ -266,19 +306,25 @@ impl FuncCompiler {
         match statement {
             Statement::Expr(x) => {
                 let amount_in_stack_before_expr = self.amount_in_stack;
-                self.compile_expr(x.expr);
-                self.code.push(Instruction::Pop);
+                // a void host function leaves nothing to pop, but .pop and .push()
+                // still leave the array there
+                let is_call = matches!(x.expr, ExprAST::Call(..));
+                let typ = self.compile_expr(x.expr);
+                if !(is_call && typ == Type::Void) {
+                    self.code.push(Instruction::Pop);
+                }
                 self.amount_in_stack = amount_in_stack_before_expr;
                 // println!("After Expr: {}", self.amount_in_stack);
             }
             Statement::Decl(x) => {
-                self.compile_expr(x.val);
+                // the type of the value is the type of the var, even for `var x = ...;`
+                let typ = self.compile_expr(x.val);
                 let len = self.scoped_vars.len();
-                if let Type::Array(_) = x.typ {
+                if let Type::Array(_) = typ {
                     self.scoped_vars[len - 1].2 += 1;
                 }
-                self.scoped_vars[len - 1].1 += x.typ.size() as u16;
-                self.track_var(x.ident, x.typ);
+                self.scoped_vars[len - 1].1 += typ.size() as u16;
+                self.track_var(x.ident, typ);
                 self.scoped_vars[len - 1].0 += 1;
                 // println!("After Decl: {}", self.amount_in_stack);
             }
@@ -291,13 +337,17 @@ impl FuncCompiler {
                 self.amount_in_stack = amount_in_stack_before_expr;
                 // println!("After Assign: {}", self.amount_in_stack);
             }
-            Statement::Return(x) => {
-                self.compile_expr(x.expr.expr);
-                self.code
-                    .push(Instruction::Ret(self.amount_in_stack as u16));
-                // it tells the vm to go down by that much in the stack
-                // println!("After Return: {}", self.amount_in_stack);
-            }
+            Statement::Return(x) => match x.expr.expr {
+                ExprAST::Call(name, args, _) if name == self.func.name => {
+                    self.compile_tail_call(args);
+                }
+                expr => {
+                    self.compile_expr(expr);
+                    // the vm drops the whole frame, so it doesn't need to know its size
+                    self.code.push(Instruction::Ret);
+                    // println!("After Return: {}", self.amount_in_stack);
+                }
+            },
             Statement::If(x) => {
                 let amount_in_stack_before_expr = self.amount_in_stack;
                 let start_ip = self.code.len();
@@ -371,6 +421,32 @@ impl FuncCompiler {
             }
         }
     }
+    // `return f(...)` inside of f doesn't need a new frame. The new args are moved
+    // over the old ones, the locals are popped, and it jumps to right after `fun`,
+    // since the call stack is already under the args like `fun` leaves it.
+    fn compile_tail_call(&mut self, args: Vec<Expression>) {
+        let amount_in_stack_before = self.amount_in_stack;
+        for arg in args {
+            self.compile_expr(arg.expr);
+        }
+        // the last arg is on top, so the params are set from the last one
+        for (id, typ) in self.func.params.iter().rev() {
+            self.code.push(Instruction::Mov(self.get_var(id).0));
+            self.code.push(Instruction::Pop);
+            self.amount_in_stack -= typ.size() as u16;
+        }
+        for scope in self.scoped_vars.iter().rev() {
+            for _ in 0..scope.0 {
+                self.code.push(Instruction::Pop);
+            }
+            for _ in 0..scope.2 {
+                self.code.push(Instruction::FreeArr);
+            }
+        }
+        self.code
+            .push(Instruction::Jmp(format!("{}-start", self.func.name)));
+        self.amount_in_stack = amount_in_stack_before;
+    }
     fn pop_the_scope(&mut self) {
         for _ in 0..self.scoped_vars[self.scoped_vars.len() - 1].0 {
             self.code.push(Instruction::Pop);
@@ -384,29 +460,17 @@ impl FuncCompiler {
     pub fn compile(mut self) -> Vec<Instruction> {
         self.code
             .push(Instruction::Label(self.func.name.to_owned()));
-        let mut cur_byte_offset = 0;
-        let mut from_top = Vec::new();
-        for (argstr, argtype) in self.func.params.iter().rev() {
-            from_top.push(cur_byte_offset);
-            cur_byte_offset += argtype.size();
-        }
-        let amount_in_stack;
-        if self.func.params.len() > 0 {
-            amount_in_stack = cur_byte_offset;
-        } else {
-            amount_in_stack = 0;
+        // the frame starts with the args, in the order they were passed
+        for (id, typ) in self.func.params.clone() {
+            self.amount_in_stack += typ.size() as u16;
+            self.track_var(id, typ);
         }
-        for ind in from_top.iter_mut().rev() {
-            *ind = amount_in_stack - *ind;
-        }
-        for ((id, typ), ind) in self.func.params.iter().zip(from_top.iter()) {
-            self.var_tracker
-                .insert(id.clone(), (*ind as u16, typ.clone()));
-        }
-        self.amount_in_stack = amount_in_stack as u16;
         // println!("At start of func: {}", self.amount_in_stack);
         self.code
             .push(Instruction::Fun(self.func.params.len() as u16));
+        // where tail calls jump back to
+        self.code
+            .push(Instruction::Label(format!("{}-start", self.func.name)));
         for st in self.func.code.clone() {
             self.compile_statement(st);
         }
@@ -415,11 +479,33 @@ impl FuncCompiler {
     }
 }
 impl CompilerComposer {
-    pub fn new(funcs: Vec<FunctionAst>) -> Self {
+    pub fn new(funcs: Vec<FunctionAst>, global_consts: Vec<GlobalConst>) -> Self {
+        CompilerComposer::new_with_host(funcs, global_consts, HostRegistry::new())
+    }
+    pub fn new_with_host(
+        funcs: Vec<FunctionAst>,
+        global_consts: Vec<GlobalConst>,
+        host: HostRegistry,
+    ) -> Self {
+        // the typechecker already made sure these can be worked out
+        let mut globals = HashMap::new();
+        for global in global_consts {
+            let lit = eval_const_expr(&global.val, &globals).unwrap();
+            globals.insert(global.ident, lit);
+        }
+        // functions main never gets to aren't compiled at all
+        let graph = CallGraph::new(&funcs);
+        let reached = graph.reachable_from("main");
+        let funcs = funcs
+            .into_iter()
+            .filter(|func| reached.contains(func.name.as_str()))
+            .collect();
         let mut init = CompilerComposer {
             consts: Vec::new(),
             pool: Vec::new(),
             funcs,
+            globals,
+            host,
         };
         println!("Creating constants . . .");
         init.create_constants();
@@ -428,6 +514,13 @@ impl CompilerComposer {
     }
 
     fn create_constants(&mut self) {
+        // sorted by name, the map's own order changes from run to run and so
+        // would the bytecode
+        let mut globals = self.globals.clone().into_iter().collect::<Vec<_>>();
+        globals.sort_by(|(name0, _), (name1, _)| name0.cmp(name1));
+        for (_, lit) in globals {
+            self.add_const(&lit);
+        }
         for func in self.funcs.clone() {
             self.create_consts_in_codevec(func.code);
         }
@@ -462,12 +555,12 @@ impl CompilerComposer {
     fn create_consts_in_expr(&mut self, expr: ExprAST) {
         match expr {
             ExprAST::Lit(lit) => self.add_const(&lit),
-            ExprAST::Var(_) => (),
+            ExprAST::Var(_, _) => (),
             ExprAST::BinOp(_, ex0, ex1) => {
                 self.create_consts_in_expr(*ex0);
                 self.create_consts_in_expr(*ex1);
             }
-            ExprAST::Call(_, exprvec) => {
+            ExprAST::Call(_, exprvec, _) => {
                 for expr in exprvec {
                     self.create_consts_in_expr(expr.expr);
                 }
@@ -521,6 +614,8 @@ impl CompilerComposer {
         let arc_consts = Arc::new(self.consts.clone());
         let arc_pool = Arc::new(self.pool.clone());
         let arc_ret = Arc::new(ret_types);
+        let arc_host = Arc::new(self.host.clone());
+        let arc_globals = Arc::new(self.globals.clone());
         let (tx, rx) = mpsc::channel();
         let mut handles = Vec::new();
         let mut funcs = self.funcs.clone();
@@ -541,11 +636,13 @@ impl CompilerComposer {
             let consts = Arc::clone(&arc_consts);
             let pool = Arc::clone(&arc_pool);
             let ret_types = Arc::clone(&arc_ret);
+            let host = Arc::clone(&arc_host);
+            let globals = Arc::clone(&arc_globals);
             let tx1 = tx.clone();
             handles.push(thread::spawn(move || {
                 let f = &func;
                 let f = f.clone();
-                let factory = FuncCompiler::new(consts, pool, ret_types, f);
+                let factory = FuncCompiler::new(consts, pool, ret_types, host, globals, f);
                 let inst_vec = factory.compile();
                 tx1.send(inst_vec).expect("emergency failure to send");
             }));
@@ -557,7 +654,8 @@ impl CompilerComposer {
             all_instructions.append(&mut rec);
         }
         if let Some(main) = main_func {
-            let factory = FuncCompiler::new(arc_consts, arc_pool, arc_ret, main);
+            let factory =
+                FuncCompiler::new(arc_consts, arc_pool, arc_ret, arc_host, arc_globals, main);
             let mut inst_vec = factory.compile();
             inst_vec.append(&mut all_instructions);
             inst_vec
@@ -569,3 +667,47 @@ impl CompilerComposer {
         (self.pool, self.consts)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::test_util::run;
+
+    #[test]
+    fn deep_tail_call() {
+        let code = "fun count(int n, int acc) -> int {
+    if n == 0 { return acc; }
+    return count(n - 1, acc + 2);
+}
+fun main() -> int { return count(60000, 0); }";
+        assert_eq!(run(code), 120000);
+    }
+
+    #[test]
+    fn tail_call_from_nested_scopes() {
+        let code = "fun walk(int n, int total) -> int {
+    int step = 2;
+    if n > 0 {
+        int extra = n % 3;
+        while extra > 5 { extra = extra - 1; }
+        if extra >= 0 {
+            int more = extra + step;
+            return walk(n - 1, total + more);
+        }
+    }
+    return total;
+}
+fun main() -> int { return walk(10, 0); }";
+        assert_eq!(run(code), 30);
+    }
+
+    #[test]
+    fn args_keep_their_order() {
+        let code = "fun sub(int a, int b) -> int { return a - b; }
+fun down(int a, int b) -> int {
+    if b == 0 { return a; }
+    return down(a - 1, b - 1);
+}
+fun main() -> int { return sub(10, 3) * 100 + down(10, 3); }";
+        assert_eq!(run(code), 707);
+    }
+}
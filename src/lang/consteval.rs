@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use super::{
+    ast::{ExprAST, GlobalConst},
+    tokens::{Literal, Operator, Type},
+};
+
+/// Works out the value of an expression before the program runs, if it can.
+/// Only literals, names in `consts`, and operators and casts on those can be worked out,
+/// anything else (or something that would crash, like dividing by zero) gives None.
+pub fn eval_const_expr(expr: &ExprAST, consts: &HashMap<String, Literal>) -> Option<Literal> {
+    match expr {
+        ExprAST::Lit(lit) => Some(lit.to_owned()),
+        ExprAST::Var(name, _) => consts.get(name).cloned(),
+        ExprAST::BinOp(op, lhs, rhs) => fold_binop(
+            op,
+            eval_const_expr(lhs, consts)?,
+            eval_const_expr(rhs, consts)?,
+        ),
+        ExprAST::Casted(typ, expr) => fold_cast(typ, eval_const_expr(expr, consts)?),
+        ExprAST::Call(_, _, _) | ExprAST::DotOp(_, _) | ExprAST::Indexed(_, _) => None,
+    }
+}
+
+/// Works out the global constants in order, so each one can use the ones above it.
+/// Bad ones are left out, the type checker is the one that reports them.
+pub fn eval_global_consts(globals: &[GlobalConst]) -> HashMap<String, Literal> {
+    let mut consts = HashMap::new();
+    for global in globals {
+        if let Some(lit) = eval_const_expr(&global.val, &consts) {
+            consts.insert(global.ident.to_owned(), lit);
+        }
+    }
+    consts
+}
+
+pub fn fold_binop(op: &Operator, lhs: Literal, rhs: Literal) -> Option<Literal> {
+    use Literal::{Bool, Dcml, Int};
+    Some(match (op, lhs, rhs) {
+        (Operator::Add, Int(x), Int(y)) => Int(x.checked_add(y)?),
+        (Operator::Sub, Int(x), Int(y)) => Int(x.checked_sub(y)?),
+        (Operator::Mult, Int(x), Int(y)) => Int(x.checked_mul(y)?),
+        (Operator::Div, Int(x), Int(y)) => Int(x.checked_div(y)?),
+        (Operator::Mod, Int(x), Int(y)) => Int(x.checked_rem(y)?),
+        (Operator::Add, Dcml(x), Dcml(y)) => Dcml(x + y),
+        (Operator::Sub, Dcml(x), Dcml(y)) => Dcml(x - y),
+        (Operator::Mult, Dcml(x), Dcml(y)) => Dcml(x * y),
+        (Operator::Div, Dcml(x), Dcml(y)) => Dcml(x / y),
+        (Operator::Eq, x, y) => Bool(x == y),
+        (Operator::NEq, x, y) => Bool(x != y),
+        (Operator::Less, Int(x), Int(y)) => Bool(x < y),
+        (Operator::LEq, Int(x), Int(y)) => Bool(x <= y),
+        (Operator::Greater, Int(x), Int(y)) => Bool(x > y),
+        (Operator::GEq, Int(x), Int(y)) => Bool(x >= y),
+        (Operator::Less, Dcml(x), Dcml(y)) => Bool(x < y),
+        (Operator::LEq, Dcml(x), Dcml(y)) => Bool(x <= y),
+        (Operator::Greater, Dcml(x), Dcml(y)) => Bool(x > y),
+        (Operator::GEq, Dcml(x), Dcml(y)) => Bool(x >= y),
+        (Operator::And | Operator::BAnd, Bool(x), Bool(y)) => Bool(x & y),
+        (Operator::Or | Operator::BOr, Bool(x), Bool(y)) => Bool(x | y),
+        (Operator::Xor | Operator::BXor, Bool(x), Bool(y)) => Bool(x ^ y),
+        (Operator::BAnd, Int(x), Int(y)) => Int(x & y),
+        (Operator::BOr, Int(x), Int(y)) => Int(x | y),
+        (Operator::BXor, Int(x), Int(y)) => Int(x ^ y),
+        (Operator::Shl, Int(x), Int(y)) => Int(x.checked_shl(y.try_into().ok()?)?),
+        (Operator::Shr, Int(x), Int(y)) => Int(x.checked_shr(y.try_into().ok()?)?),
+        (Operator::Pow, Int(x), Int(y)) => Int(x.checked_pow(y.try_into().ok()?)?),
+        (Operator::Pow, Dcml(x), Dcml(y)) => Dcml(x.powf(y)),
+        _ => return None,
+    })
+}
+
+pub fn fold_cast(typ: &Type, lit: Literal) -> Option<Literal> {
+    Some(match (lit, typ) {
+        (Literal::Int(x), Type::Dcml) => Literal::Dcml(x as f64),
+        (Literal::Dcml(x), Type::Int) => Literal::Int(x as i32),
+        (Literal::Bool(x), Type::Int) => Literal::Int(x as i32),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fold_binop, fold_cast};
+    use crate::lang::{
+        test_util::codes,
+        tokens::{Literal, Operator, Type},
+    };
+
+    #[test]
+    fn folds_what_cant_crash() {
+        use Literal::{Bool, Dcml, Int};
+        assert_eq!(fold_binop(&Operator::Add, Int(2), Int(3)), Some(Int(5)));
+        assert_eq!(
+            fold_binop(&Operator::Less, Dcml(1.5), Dcml(2.0)),
+            Some(Bool(true))
+        );
+        assert_eq!(fold_cast(&Type::Int, Dcml(2.7)), Some(Int(2)));
+        // these crash when they run, so they are left for the VM
+        assert_eq!(fold_binop(&Operator::Div, Int(1), Int(0)), None);
+        assert_eq!(fold_binop(&Operator::Add, Int(i32::MAX), Int(1)), None);
+        assert_eq!(fold_binop(&Operator::Shl, Int(1), Int(-1)), None);
+        assert_eq!(fold_binop(&Operator::Pow, Int(2), Int(-1)), None);
+    }
+
+    #[test]
+    fn divide_by_constant_zero() {
+        let code = "const int NONE = 2 - 2;
+fun main() -> int {
+    int x = 10;
+    int y = x % NONE;
+    return x / (1 - 1) + y;
+}";
+        assert_eq!(codes(code), vec!["T0010", "T0010"]);
+    }
+
+    #[test]
+    fn constant_condition() {
+        let code = "const int LIMIT = 3;
+fun main() -> int {
+    if LIMIT > 2 { return 1; }
+    return 0;
+}";
+        assert_eq!(codes(code), vec!["W0008", "W0006"]);
+    }
+}
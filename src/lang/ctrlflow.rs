@@ -1,15 +1,29 @@
 use crate::lang::errors::ErrorType;
 
 use super::{
-    ast::{FunctionAst, IfBlock, Statement, WhileBlock},
-    errors::CompileError,
+    ast::{ExprAST, Expression, FunctionAst, GlobalConst, IfBlock, Loc, Statement, WhileBlock},
+    consteval::{eval_const_expr, eval_global_consts},
+    errors::{CompileError, Diagnostic},
+    tokens::Literal,
+};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::{Rc, Weak},
 };
-use std::rc::Rc;
 
 struct BasicBlock {
     code: Vec<Statement>,
     inputs: OneOrTwo<BasicBlock>,
-    returns: bool,
+    // the blocks control can go to next, filled in as they are made.
+    // Weak, because they point back here through `inputs`
+    successors: RefCell<Vec<Weak<BasicBlock>>>,
+    // nothing after this block runs, it returns or loops forever
+    exits: bool,
+    // the first and last statements that belong to this block,
+    // counting the if or while that ends it
+    start: Option<Loc>,
+    last: Option<Loc>,
 }
 
 enum OneOrTwo<T> {
@@ -18,81 +32,185 @@ enum OneOrTwo<T> {
     Two(Rc<T>, Rc<T>),
 }
 
-pub fn check_for_returns(ast: Vec<FunctionAst>) -> Result<(), Vec<CompileError>> {
+/// Errors for functions that might not return, or warnings for code that can never run.
+/// Conditions that are always true or always false (using literals and `global_consts`)
+/// are followed the one way they can go, so `while true { ... }` never gets to its end.
+pub fn check_for_returns(
+    ast: Vec<FunctionAst>,
+    global_consts: &[GlobalConst],
+) -> Result<Vec<Diagnostic>, Vec<CompileError>> {
+    let consts = eval_global_consts(global_consts);
     let mut errvec = Vec::new();
+    let mut warnings = Vec::new();
     for func in ast {
-        let correct = check_for_ret(Rc::new(create_basic_blocks(func.code, OneOrTwo::None)));
+        find_constant_conds(&func.code, &consts, &mut warnings);
+        let end = create_basic_blocks(func.code, OneOrTwo::None, &consts);
+        let correct = check_for_ret(Rc::clone(&end));
         if !correct {
-            errvec.push(CompileError {
-                e_type: ErrorType::ControlFlowError(format!(
-                    "The function '{}' might not return",
-                    func.name
-                )),
-                line: func.loc.line,
-                col: func.loc.col,
-            });
+            // points at the last thing before the end on a way that doesn't return
+            let loc = find_fall_through(&end).unwrap_or(func.loc);
+            errvec.push(CompileError::at(
+                ErrorType::MightNotReturn { name: func.name },
+                &loc,
+            ));
         }
+        warnings.append(&mut find_unreachable(&end));
     }
     if errvec.len() == 0 {
-        Ok(())
+        Ok(warnings)
     } else {
         Err(errvec)
     }
 }
 
-fn create_basic_blocks(funccode: Vec<Statement>, inputs: OneOrTwo<BasicBlock>) -> BasicBlock {
+// makes the block and tells its inputs about it
+fn new_block(
+    code: Vec<Statement>,
+    inputs: OneOrTwo<BasicBlock>,
+    exits: bool,
+    start: Option<Loc>,
+    last: Option<Loc>,
+) -> Rc<BasicBlock> {
+    let block = Rc::new(BasicBlock {
+        code,
+        inputs,
+        successors: RefCell::new(Vec::new()),
+        exits,
+        start,
+        last,
+    });
+    for input in block.inputs.iter() {
+        // nothing comes after a return
+        if !input.exits {
+            input.successors.borrow_mut().push(Rc::downgrade(&block));
+        }
+    }
+    block
+}
+
+fn create_basic_blocks(
+    funccode: Vec<Statement>,
+    inputs: OneOrTwo<BasicBlock>,
+    consts: &HashMap<String, Literal>,
+) -> Rc<BasicBlock> {
     let mut buf: Vec<Statement> = Vec::new();
-    let mut cur_ret = false;
+    let mut start = None;
     let mut cur_inputs: OneOrTwo<BasicBlock> = inputs;
     for statement in &funccode {
+        start = start.or(Some(statement.loc()));
+        let last = Some(statement.loc());
         match statement {
             Statement::If(x) => {
-                let block = BasicBlock {
-                    code: buf,
-                    inputs: cur_inputs,
-                    returns: cur_ret,
-                };
-                cur_inputs = create_if_basic(Rc::new(block), x.to_owned());
+                let block = new_block(buf, cur_inputs, false, start, last);
+                cur_inputs = create_if_basic(block, x.to_owned(), consts);
                 buf = Vec::new();
-                cur_ret = false;
+                start = None;
             }
             Statement::While(x) => {
-                let rcblock = Rc::new(BasicBlock {
-                    code: buf,
-                    inputs: cur_inputs,
-                    returns: cur_ret,
-                });
-                let other_block = create_while_basic(Rc::clone(&rcblock), x.to_owned());
-                cur_inputs = OneOrTwo::Two(Rc::clone(&rcblock), Rc::new(other_block));
+                let rcblock = new_block(buf, cur_inputs, false, start, last);
+                cur_inputs = create_while_basic(rcblock, x.to_owned(), consts);
                 buf = Vec::new();
-                cur_ret = false;
+                start = None;
             }
             Statement::Return(x) => {
-                cur_ret = true;
+                // anything after a return goes in a new block that nothing leads to
                 buf.push(Statement::Return(x.to_owned()));
+                cur_inputs = OneOrTwo::One(new_block(buf, cur_inputs, true, start, last));
+                buf = Vec::new();
+                start = None;
             }
             x => buf.push(x.to_owned()),
         }
     }
-    return BasicBlock {
-        code: buf,
-        inputs: cur_inputs,
-        returns: cur_ret,
-    };
+    let last = buf.last().map(|statement| statement.loc());
+    return new_block(buf, cur_inputs, false, start, last);
+}
+
+// an empty block that control stops at, for the way a constant condition can't go
+fn dead_end(input: OneOrTwo<BasicBlock>) -> Rc<BasicBlock> {
+    new_block(Vec::new(), input, true, None, None)
 }
 
-fn create_if_basic(input: Rc<BasicBlock>, if_blk: IfBlock) -> OneOrTwo<BasicBlock> {
-    let t_block = create_basic_blocks(if_blk.tcode.to_owned(), OneOrTwo::One(Rc::clone(&input)));
-    let e_block = create_basic_blocks(if_blk.ecode.to_owned(), OneOrTwo::One(Rc::clone(&input)));
-    return OneOrTwo::Two(Rc::new(t_block), Rc::new(e_block));
+fn const_cond(cond: &Expression, consts: &HashMap<String, Literal>) -> Option<bool> {
+    match eval_const_expr(&cond.expr, consts) {
+        Some(Literal::Bool(val)) => Some(val),
+        _ => None,
+    }
 }
 
-fn create_while_basic(inputs: Rc<BasicBlock>, while_blk: WhileBlock) -> BasicBlock {
-    return create_basic_blocks(while_blk.code.to_owned(), OneOrTwo::One(Rc::clone(&inputs)));
+// Conditions that always come out the same are probably mistakes, like `if 1 == 1`.
+// A lone literal or constant (`while true`, `if DEBUG`) is left alone, that is on purpose.
+fn find_constant_conds(
+    block: &[Statement],
+    consts: &HashMap<String, Literal>,
+    warnings: &mut Vec<Diagnostic>,
+) {
+    for statement in block {
+        match statement {
+            Statement::If(ifblock) => {
+                warnings.extend(constant_cond_warning(&ifblock.cond, consts));
+                find_constant_conds(&ifblock.tcode, consts, warnings);
+                find_constant_conds(&ifblock.ecode, consts, warnings);
+            }
+            Statement::While(whileblock) => {
+                warnings.extend(constant_cond_warning(&whileblock.cond, consts));
+                find_constant_conds(&whileblock.code, consts, warnings);
+            }
+            _ => (),
+        }
+    }
+}
+
+fn constant_cond_warning(
+    cond: &Expression,
+    consts: &HashMap<String, Literal>,
+) -> Option<Diagnostic> {
+    if let ExprAST::Lit(_) | ExprAST::Var(_, _) = cond.expr {
+        return None;
+    }
+    let value = const_cond(cond, consts)?;
+    Some(CompileError::at(
+        ErrorType::ConstantCondition { value },
+        &cond.loc,
+    ))
+}
+
+fn create_if_basic(
+    input: Rc<BasicBlock>,
+    if_blk: IfBlock,
+    consts: &HashMap<String, Literal>,
+) -> OneOrTwo<BasicBlock> {
+    let (t_input, e_input) = match const_cond(&if_blk.cond, consts) {
+        Some(true) => (Rc::clone(&input), dead_end(OneOrTwo::One(input))),
+        Some(false) => (dead_end(OneOrTwo::One(Rc::clone(&input))), input),
+        None => (Rc::clone(&input), input),
+    };
+    let t_block = create_basic_blocks(if_blk.tcode.to_owned(), OneOrTwo::One(t_input), consts);
+    let e_block = create_basic_blocks(if_blk.ecode.to_owned(), OneOrTwo::One(e_input), consts);
+    return OneOrTwo::Two(t_block, e_block);
+}
+
+// gives the inputs for whatever comes after the loop
+fn create_while_basic(
+    input: Rc<BasicBlock>,
+    while_blk: WhileBlock,
+    consts: &HashMap<String, Literal>,
+) -> OneOrTwo<BasicBlock> {
+    let cond = const_cond(&while_blk.cond, consts);
+    let body_input = match cond {
+        Some(false) => dead_end(OneOrTwo::One(Rc::clone(&input))),
+        _ => Rc::clone(&input),
+    };
+    let body = create_basic_blocks(while_blk.code.to_owned(), OneOrTwo::One(body_input), consts);
+    match cond {
+        // there is no break, so the only way out is a return
+        Some(true) => OneOrTwo::One(dead_end(OneOrTwo::Two(input, body))),
+        _ => OneOrTwo::Two(input, body),
+    }
 }
 
 fn check_for_ret(blk: Rc<BasicBlock>) -> bool {
-    if blk.returns {
+    if blk.exits {
         return true;
     } else {
         return match blk.inputs {
@@ -104,3 +222,69 @@ fn check_for_ret(blk: Rc<BasicBlock>) -> bool {
         };
     }
 }
+
+// goes back from the end along a way that doesn't return, to the last statement on it
+fn find_fall_through(blk: &Rc<BasicBlock>) -> Option<Loc> {
+    if blk.last.is_some() {
+        return blk.last;
+    }
+    blk.inputs
+        .iter()
+        .find(|input| !check_for_ret(Rc::clone(input)))
+        .and_then(find_fall_through)
+}
+
+// Every block can be found by going back through the inputs from the last one,
+// and the ones that can't be reached going forward from the first one never run.
+// Only the first dead block in a row gets a warning.
+fn find_unreachable(end: &Rc<BasicBlock>) -> Vec<Diagnostic> {
+    let mut all_blocks = Vec::new();
+    let mut seen = HashSet::new();
+    let mut to_visit = vec![Rc::clone(end)];
+    while let Some(block) = to_visit.pop() {
+        if seen.insert(Rc::as_ptr(&block)) {
+            to_visit.extend(block.inputs.iter().cloned());
+            all_blocks.push(block);
+        }
+    }
+    let entry = all_blocks
+        .iter()
+        .find(|block| matches!(block.inputs, OneOrTwo::None))
+        .unwrap();
+    let mut reached = HashSet::new();
+    let mut to_visit = vec![Rc::clone(entry)];
+    while let Some(block) = to_visit.pop() {
+        if reached.insert(Rc::as_ptr(&block)) {
+            to_visit.extend(block.successors.borrow().iter().filter_map(Weak::upgrade));
+        }
+    }
+    let mut warnings = all_blocks
+        .iter()
+        .filter(|block| !reached.contains(&Rc::as_ptr(block)))
+        .filter(|block| !after_dead_code(block, &reached))
+        .filter_map(|block| block.start)
+        .map(|loc| CompileError::at(ErrorType::UnreachableCode, &loc))
+        .collect::<Vec<Diagnostic>>();
+    warnings.sort_by_key(|warning| (warning.line, warning.col));
+    warnings
+}
+
+// if dead code comes before this block, the warning is already there.
+// The empty blocks left after a return don't count, they have no code to warn about
+fn after_dead_code(block: &BasicBlock, reached: &HashSet<*const BasicBlock>) -> bool {
+    block.inputs.iter().any(|input| {
+        !reached.contains(&Rc::as_ptr(input))
+            && (input.start.is_some() || after_dead_code(input, reached))
+    })
+}
+
+impl<T> OneOrTwo<T> {
+    fn iter(&self) -> impl Iterator<Item = &Rc<T>> {
+        let (first, second) = match self {
+            OneOrTwo::None => (None, None),
+            OneOrTwo::One(x) => (Some(x), None),
+            OneOrTwo::Two(x, y) => (Some(x), Some(y)),
+        };
+        first.into_iter().chain(second)
+    }
+}
@@ -1,19 +1,673 @@
+use super::{
+    ast::Loc,
+    builtins::builtin_signatures,
+    explain::{fill, message_template},
+    tokens::{Operator, Type},
+};
+
+/// Errors and warnings are the same shape, `e_type.severity()` tells them apart.
+pub type Diagnostic = CompileError;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    // the code can't run
+    Error,
+    // the code runs, but it is probably not what was meant
+    Warning,
+}
+
 #[derive(Clone, Debug)]
 pub struct CompileError {
     pub e_type: ErrorType,
     pub line: u32,
     pub col: u32,
+    // the error covers the code up to just before here
+    pub end_line: u32,
+    pub end_col: u32,
+    // names that were probably meant, closest first ("Did you mean ...?")
+    pub suggestions: Vec<String>,
+    // other places the error is about, like where a const was made, and what each one is.
+    // A boxed slice, so errors stay small enough to pass around in a `Result`
+    pub labels: Box<[(Loc, String)]>,
 }
 impl CompileError {
+    // an error at one spot, for when there is no code to point at
     pub fn new(e_type: ErrorType, line: u32, col: u32) -> Self {
-        CompileError { e_type, line, col }
+        CompileError::at(e_type, &Loc::new(line, col))
+    }
+    pub fn at(e_type: ErrorType, loc: &Loc) -> Self {
+        CompileError {
+            e_type,
+            line: loc.line,
+            col: loc.col,
+            end_line: loc.end_line,
+            end_col: loc.end_col,
+            suggestions: Vec::new(),
+            labels: Box::new([]),
+        }
+    }
+    pub fn with_suggestions(mut self, suggestions: Vec<String>) -> Self {
+        self.suggestions = suggestions;
+        self
+    }
+    pub fn with_label(mut self, loc: &Loc, label: String) -> Self {
+        let mut labels = self.labels.into_vec();
+        labels.push((*loc, label));
+        self.labels = labels.into_boxed_slice();
+        self
     }
+    /// The one line message, like "Could not find variable 'x', did you mean 'y'?"
+    pub fn message(&self) -> String {
+        let mut msg = fill(message_template(self.e_type.code()), &self.e_type.fields());
+        if let Some(close) = self.suggestions.first() {
+            msg += ", ";
+            msg += &fill(
+                message_template("did_you_mean"),
+                &[("name", close.to_owned())],
+            );
+        }
+        msg
+    }
+    /// Shows the error with the line of code it is on, underlined, and then
+    /// any other places it is about:
+    /// ```text
+    /// Name error V0010 on line 3:
+    ///   3 |     x = 2;
+    ///     |     ^ Cannot change 'x', it was declared const at line 2, column 15
+    ///   2 |     const int x = 1;
+    ///     |               ^ 'x' was made const here
+    /// ```
+    pub fn render(&self, code: &str) -> String {
+        let kind = message_template(self.e_type.category());
+        let msg = self.message();
+        let here = Loc {
+            line: self.line,
+            col: self.col,
+            end_line: self.end_line,
+            end_col: self.end_col,
+        };
+        // the line numbers all line up with the biggest one
+        let width = self
+            .labels
+            .iter()
+            .map(|(loc, _)| loc.line)
+            .chain([self.line])
+            .max()
+            .unwrap_or_default()
+            .to_string()
+            .len();
+        let Some(snippet) = underline(code, &here, &msg, width) else {
+            // errors without a place in the code, like crashes
+            return format!("{} {}: {}", kind, self.e_type.code(), msg);
+        };
+        let mut ans = format!(
+            "{} {} {}:\n{}",
+            kind,
+            self.e_type.code(),
+            fill(
+                message_template("on_line"),
+                &[("line", self.line.to_string())]
+            ),
+            snippet
+        );
+        for (loc, label) in self.labels.iter() {
+            if let Some(snippet) = underline(code, loc, label, width) {
+                ans += "\n";
+                ans += &snippet;
+            }
+        }
+        if self.suggestions.len() > 1 {
+            ans += &format!(
+                "\n {} = {}",
+                " ".repeat(width),
+                fill(
+                    message_template("maybe"),
+                    &[("names", self.suggestions.join(", "))]
+                )
+            );
+        }
+        ans
+    }
+}
+// the line of code at `loc`, with `loc` underlined and `label` after it,
+// or None if the code has no line there
+fn underline(code: &str, loc: &Loc, label: &str, width: usize) -> Option<String> {
+    let src_line = code.lines().nth((loc.line as usize).wrapping_sub(1))?;
+    let src_line = src_line.trim_end();
+    // keep the tabs from the code so the carets line up
+    let start = (loc.col as usize).saturating_sub(1);
+    let padding = src_line
+        .chars()
+        .take(start)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+    // errors going over multiple lines are underlined to the end of the first one
+    let end = if loc.end_line == loc.line {
+        (loc.end_col as usize).saturating_sub(1)
+    } else {
+        src_line.chars().count()
+    };
+    let carets = "^".repeat(end.saturating_sub(start).max(1));
+    Some(format!(
+        " {:>width$} | {}\n {} | {}{} {}",
+        loc.line,
+        src_line,
+        " ".repeat(width),
+        padding,
+        carets,
+        label
+    ))
+}
+/// All of the errors rendered, with a blank line between each one.
+pub fn render_errors(errors: &[CompileError], code: &str) -> String {
+    errors
+        .iter()
+        .map(|err| err.render(code))
+        .collect::<Vec<String>>()
+        .join("\n\n")
 }
-#[derive(Clone, Debug, PartialEq, Eq)]
+
+/// Everything that can go wrong. Each one has a code that never changes (see `code`),
+/// so it can be looked up with `explain`.
+#[derive(Clone, Debug, PartialEq)]
 pub enum ErrorType {
-    LexingError(String),
-    ParsingError(String),
-    VariableError(String),
-    ControlFlowError(String),
-    TypeError(String),
+    // lexing
+    TwoPointsInNumber,
+    BackslashAtEnd,
+    BadEscape {
+        escape: char,
+    },
+    UnfinishedString,
+    UnknownSymbol {
+        symbol: String,
+    },
+    UnknownChar {
+        found: char,
+    },
+    // parsing
+    ExpectedGlobalConstType,
+    ExpectedLocalConstType,
+    ExpectedName {
+        after: String,
+    },
+    ExpectedAssign {
+        name: String,
+    },
+    ExpectedSemicolon,
+    ExpectedFun,
+    UnknownWord {
+        word: String,
+    },
+    ExpectedLeftParen {
+        name: String,
+    },
+    ExpectedParamType,
+    ExpectedParamSeparator,
+    ExpectedArrow,
+    ExpectedReturnType,
+    ExpectedBlock {
+        keyword: String,
+    },
+    UnclosedBlock,
+    UnexpectedToken,
+    ExpectedCallSeparator,
+    ExpectedCloseParen,
+    ExpectedCloseBracket,
+    ExpectedValue,
+    ExpectedParenAfterPush,
+    UnknownDotOp {
+        name: String,
+    },
+    ExpectedCastType,
+    NoMain,
+    // names
+    UnknownVariable {
+        name: String,
+    },
+    UsedBeforeDeclared {
+        name: String,
+        decl_line: u32,
+    },
+    OutOfScope {
+        name: String,
+        decl_line: u32,
+    },
+    UnknownFunction {
+        name: String,
+    },
+    DuplicateFunction {
+        name: String,
+    },
+    DuplicateParam {
+        func: String,
+        name: String,
+    },
+    DuplicateVariable {
+        name: String,
+    },
+    NameIsConst {
+        name: String,
+    },
+    AssignToGlobalConst {
+        name: String,
+    },
+    AssignToConst {
+        name: String,
+        decl_line: u32,
+        decl_col: u32,
+    },
+    BuiltinName {
+        name: String,
+    },
+    HostName {
+        name: String,
+    },
+    DuplicateConst {
+        name: String,
+    },
+    KeywordClash {
+        name: String,
+    },
+    // types
+    BadBinOp {
+        op: Operator,
+        left: Type,
+        right: Type,
+    },
+    // the signatures it could have been are looked up from the name, to keep errors small
+    BadBuiltinArgs {
+        name: String,
+        args: Vec<Type>,
+    },
+    WrongArgCount {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    WrongArgType {
+        name: String,
+        expected: Type,
+        found: Type,
+    },
+    BadCast {
+        from: Type,
+        to: Type,
+    },
+    WrongVarType {
+        name: String,
+        expected: Type,
+        found: Type,
+    },
+    WrongConstType {
+        name: String,
+        expected: Type,
+        found: Type,
+    },
+    WrongReturnType {
+        expected: Type,
+        found: Type,
+    },
+    ConstNotKnown {
+        name: String,
+    },
+    DivByZero,
+    NegativeIndex {
+        index: i32,
+    },
+    NotAnArray {
+        found: Type,
+    },
+    IndexNotInt {
+        found: Type,
+    },
+    NoValue {
+        name: String,
+    },
+    // control flow
+    MightNotReturn {
+        name: String,
+    },
+    NeedsRecursion,
+    // crashes while running
+    NegativeShift {
+        amount: i32,
+    },
+    ShiftTooBig {
+        amount: i32,
+    },
+    NegativePower {
+        exp: i32,
+    },
+    PowerOverflow {
+        base: i32,
+        exp: i32,
+    },
+    AbsOverflow {
+        value: i32,
+    },
+    NotAnInt {
+        name: String,
+        value: f64,
+    },
+    NegativeSqrt {
+        value: f64,
+    },
+    BadRandomRange {
+        low: i32,
+        high: i32,
+    },
+    HostWrongType {
+        name: String,
+        expected: Type,
+        found: Type,
+    },
+    HostFailed {
+        name: String,
+        reason: String,
+    },
+    // warnings, the code still runs
+    UnusedVariable {
+        name: String,
+    },
+    UnusedParam {
+        func: String,
+        name: String,
+    },
+    UnusedFunction {
+        name: String,
+    },
+    SelfAssign {
+        name: String,
+    },
+    UnreachableCode,
+    InfiniteLoop,
+    ConstantCondition {
+        value: bool,
+    },
+}
+impl ErrorType {
+    /// The code for this kind of error. These are never reused or changed,
+    /// new errors get new codes.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorType::TwoPointsInNumber => "L0001",
+            ErrorType::BackslashAtEnd => "L0002",
+            ErrorType::BadEscape { .. } => "L0003",
+            ErrorType::UnfinishedString => "L0004",
+            ErrorType::UnknownSymbol { .. } => "L0005",
+            ErrorType::UnknownChar { .. } => "L0006",
+            ErrorType::ExpectedGlobalConstType => "P0001",
+            ErrorType::ExpectedLocalConstType => "P0002",
+            ErrorType::ExpectedName { .. } => "P0003",
+            ErrorType::ExpectedAssign { .. } => "P0004",
+            ErrorType::ExpectedSemicolon => "P0005",
+            ErrorType::ExpectedFun => "P0006",
+            ErrorType::UnknownWord { .. } => "P0007",
+            ErrorType::ExpectedLeftParen { .. } => "P0008",
+            ErrorType::ExpectedParamType => "P0009",
+            ErrorType::ExpectedParamSeparator => "P0010",
+            ErrorType::ExpectedArrow => "P0011",
+            ErrorType::ExpectedReturnType => "P0012",
+            ErrorType::ExpectedBlock { .. } => "P0013",
+            ErrorType::UnclosedBlock => "P0014",
+            ErrorType::UnexpectedToken => "P0015",
+            ErrorType::ExpectedCallSeparator => "P0016",
+            ErrorType::ExpectedCloseParen => "P0017",
+            ErrorType::ExpectedCloseBracket => "P0018",
+            ErrorType::ExpectedValue => "P0019",
+            ErrorType::ExpectedParenAfterPush => "P0020",
+            ErrorType::UnknownDotOp { .. } => "P0021",
+            ErrorType::ExpectedCastType => "P0022",
+            ErrorType::NoMain => "P0023",
+            ErrorType::UnknownVariable { .. } => "V0001",
+            ErrorType::UsedBeforeDeclared { .. } => "V0002",
+            ErrorType::OutOfScope { .. } => "V0003",
+            ErrorType::UnknownFunction { .. } => "V0004",
+            ErrorType::DuplicateFunction { .. } => "V0005",
+            ErrorType::DuplicateParam { .. } => "V0006",
+            ErrorType::DuplicateVariable { .. } => "V0007",
+            ErrorType::NameIsConst { .. } => "V0008",
+            ErrorType::AssignToGlobalConst { .. } => "V0009",
+            ErrorType::AssignToConst { .. } => "V0010",
+            ErrorType::BuiltinName { .. } => "V0011",
+            ErrorType::HostName { .. } => "V0012",
+            ErrorType::DuplicateConst { .. } => "V0013",
+            ErrorType::KeywordClash { .. } => "V0014",
+            ErrorType::BadBinOp { .. } => "T0001",
+            ErrorType::BadBuiltinArgs { .. } => "T0002",
+            ErrorType::WrongArgCount { .. } => "T0003",
+            ErrorType::WrongArgType { .. } => "T0004",
+            ErrorType::BadCast { .. } => "T0005",
+            ErrorType::WrongVarType { .. } => "T0006",
+            ErrorType::WrongConstType { .. } => "T0007",
+            ErrorType::WrongReturnType { .. } => "T0008",
+            ErrorType::ConstNotKnown { .. } => "T0009",
+            ErrorType::DivByZero => "T0010",
+            ErrorType::NegativeIndex { .. } => "T0011",
+            ErrorType::NotAnArray { .. } => "T0012",
+            ErrorType::IndexNotInt { .. } => "T0013",
+            ErrorType::NoValue { .. } => "T0014",
+            ErrorType::MightNotReturn { .. } => "C0001",
+            ErrorType::NeedsRecursion => "C0002",
+            ErrorType::NegativeShift { .. } => "R0001",
+            ErrorType::ShiftTooBig { .. } => "R0002",
+            ErrorType::NegativePower { .. } => "R0003",
+            ErrorType::NegativeSqrt { .. } => "R0004",
+            ErrorType::BadRandomRange { .. } => "R0005",
+            ErrorType::HostWrongType { .. } => "R0006",
+            ErrorType::HostFailed { .. } => "R0007",
+            ErrorType::PowerOverflow { .. } => "R0008",
+            ErrorType::AbsOverflow { .. } => "R0009",
+            ErrorType::NotAnInt { .. } => "R0010",
+            ErrorType::UnusedVariable { .. } => "W0001",
+            ErrorType::UnusedParam { .. } => "W0002",
+            ErrorType::UnusedFunction { .. } => "W0003",
+            ErrorType::SelfAssign { .. } => "W0005",
+            ErrorType::UnreachableCode => "W0006",
+            ErrorType::InfiniteLoop => "W0007",
+            ErrorType::ConstantCondition { .. } => "W0008",
+        }
+    }
+    pub fn severity(&self) -> Severity {
+        if self.code().starts_with('W') {
+            Severity::Warning
+        } else {
+            Severity::Error
+        }
+    }
+    // the first letter of the code says what part of the compiler found it
+    pub fn category(&self) -> &'static str {
+        if self.severity() == Severity::Warning {
+            return "warning";
+        }
+        match self.code().as_bytes()[0] {
+            b'L' => "lexing_error",
+            b'P' => "parsing_error",
+            b'V' => "variable_error",
+            b'T' => "type_error",
+            b'C' => "control_flow_error",
+            _ => "runtime_error",
+        }
+    }
+    /// The values that get put into the message, by name.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        fn types(typs: &[Type]) -> String {
+            let names = typs
+                .iter()
+                .map(|typ| typ.to_string())
+                .collect::<Vec<String>>();
+            format!("({})", names.join(", "))
+        }
+        match self {
+            ErrorType::BadEscape { escape } => vec![("escape", escape.to_string())],
+            ErrorType::UnknownSymbol { symbol } => vec![("symbol", symbol.to_owned())],
+            ErrorType::UnknownChar { found } => vec![("found", found.to_string())],
+            ErrorType::ExpectedName { after } => vec![("after", after.to_owned())],
+            ErrorType::UnknownWord { word } => vec![("word", word.to_owned())],
+            ErrorType::ExpectedBlock { keyword } => vec![("keyword", keyword.to_owned())],
+            ErrorType::UnknownDotOp { name }
+            | ErrorType::ExpectedAssign { name }
+            | ErrorType::ExpectedLeftParen { name }
+            | ErrorType::UnknownVariable { name }
+            | ErrorType::UnknownFunction { name }
+            | ErrorType::DuplicateFunction { name }
+            | ErrorType::DuplicateVariable { name }
+            | ErrorType::NameIsConst { name }
+            | ErrorType::AssignToGlobalConst { name }
+            | ErrorType::BuiltinName { name }
+            | ErrorType::HostName { name }
+            | ErrorType::DuplicateConst { name }
+            | ErrorType::KeywordClash { name }
+            | ErrorType::ConstNotKnown { name }
+            | ErrorType::NoValue { name }
+            | ErrorType::MightNotReturn { name }
+            | ErrorType::UnusedVariable { name }
+            | ErrorType::UnusedFunction { name }
+            | ErrorType::SelfAssign { name } => vec![("name", name.to_owned())],
+            ErrorType::UsedBeforeDeclared { name, decl_line }
+            | ErrorType::OutOfScope { name, decl_line } => {
+                vec![
+                    ("name", name.to_owned()),
+                    ("decl_line", decl_line.to_string()),
+                ]
+            }
+            ErrorType::DuplicateParam { func, name } | ErrorType::UnusedParam { func, name } => {
+                vec![("func", func.to_owned()), ("name", name.to_owned())]
+            }
+            ErrorType::AssignToConst {
+                name,
+                decl_line,
+                decl_col,
+            } => vec![
+                ("name", name.to_owned()),
+                ("decl_line", decl_line.to_string()),
+                ("decl_col", decl_col.to_string()),
+            ],
+            ErrorType::BadBinOp { op, left, right } => vec![
+                ("op", op.to_string()),
+                ("left", left.to_string()),
+                ("right", right.to_string()),
+            ],
+            ErrorType::BadBuiltinArgs { name, args } => vec![
+                ("name", name.to_owned()),
+                ("args", types(args)),
+                (
+                    "options",
+                    builtin_signatures(name)
+                        .into_iter()
+                        .map(types)
+                        .collect::<Vec<String>>()
+                        .join(" / "),
+                ),
+            ],
+            ErrorType::WrongArgCount {
+                name,
+                expected,
+                found,
+            } => vec![
+                ("name", name.to_owned()),
+                ("expected", expected.to_string()),
+                ("found", found.to_string()),
+            ],
+            ErrorType::WrongArgType {
+                name,
+                expected,
+                found,
+            }
+            | ErrorType::WrongVarType {
+                name,
+                expected,
+                found,
+            }
+            | ErrorType::WrongConstType {
+                name,
+                expected,
+                found,
+            }
+            | ErrorType::HostWrongType {
+                name,
+                expected,
+                found,
+            } => vec![
+                ("name", name.to_owned()),
+                ("expected", expected.to_string()),
+                ("found", found.to_string()),
+            ],
+            ErrorType::BadCast { from, to } => {
+                vec![("from", from.to_string()), ("to", to.to_string())]
+            }
+            ErrorType::WrongReturnType { expected, found } => vec![
+                ("expected", expected.to_string()),
+                ("found", found.to_string()),
+            ],
+            ErrorType::NegativeShift { amount } | ErrorType::ShiftTooBig { amount } => {
+                vec![("amount", amount.to_string())]
+            }
+            ErrorType::NegativePower { exp } => vec![("exp", exp.to_string())],
+            ErrorType::PowerOverflow { base, exp } => {
+                vec![("base", base.to_string()), ("exp", exp.to_string())]
+            }
+            ErrorType::NegativeIndex { index } => vec![("index", index.to_string())],
+            ErrorType::NotAnArray { found } | ErrorType::IndexNotInt { found } => {
+                vec![("found", found.to_string())]
+            }
+            ErrorType::ConstantCondition { value } => vec![("value", value.to_string())],
+            ErrorType::NegativeSqrt { value } => vec![("value", value.to_string())],
+            ErrorType::AbsOverflow { value } => vec![("value", value.to_string())],
+            ErrorType::NotAnInt { name, value } => {
+                vec![("name", name.to_owned()), ("value", value.to_string())]
+            }
+            ErrorType::BadRandomRange { low, high } => {
+                vec![("low", low.to_string()), ("high", high.to_string())]
+            }
+            ErrorType::HostFailed { name, reason } => {
+                vec![("name", name.to_owned()), ("reason", reason.to_owned())]
+            }
+            ErrorType::TwoPointsInNumber
+            | ErrorType::BackslashAtEnd
+            | ErrorType::UnfinishedString
+            | ErrorType::ExpectedGlobalConstType
+            | ErrorType::ExpectedLocalConstType
+            | ErrorType::ExpectedSemicolon
+            | ErrorType::ExpectedFun
+            | ErrorType::ExpectedParamType
+            | ErrorType::ExpectedParamSeparator
+            | ErrorType::ExpectedArrow
+            | ErrorType::ExpectedReturnType
+            | ErrorType::UnclosedBlock
+            | ErrorType::UnexpectedToken
+            | ErrorType::ExpectedCallSeparator
+            | ErrorType::ExpectedCloseParen
+            | ErrorType::ExpectedCloseBracket
+            | ErrorType::ExpectedValue
+            | ErrorType::ExpectedParenAfterPush
+            | ErrorType::ExpectedCastType
+            | ErrorType::NoMain
+            | ErrorType::UnreachableCode
+            | ErrorType::InfiniteLoop
+            | ErrorType::DivByZero
+            | ErrorType::NeedsRecursion => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_errors;
+    use crate::lang::test_util::errors;
+
+    #[test]
+    fn const_assignment_points_at_the_declaration() {
+        let code = "fun main() -> int {
+    const int x = 1;
+    x = 2;
+    return x;
+}";
+        let rendered = render_errors(&errors(code), code);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[1], " 3 |     x = 2;");
+        assert!(lines[2].starts_with("   |     ^ "), "{}", rendered);
+        assert_eq!(lines[3], " 2 |     const int x = 1;");
+        assert_eq!(lines[4], "   |               ^ 'x' was made const here");
+    }
 }
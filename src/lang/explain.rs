@@ -0,0 +1,121 @@
+use std::sync::{
+    LazyLock,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use json::JsonValue;
+
+// `LOCALES`, the (name, json) of every file in ./locales, English first
+include!(concat!(env!("OUT_DIR"), "/locales.rs"));
+
+/// All the messages in one language. The json has "phrases" (words used around
+/// messages, and the GUI's text) and "errors" (a message, explanation and
+/// example for every error code, with `{field}`s filled in from `ErrorType::fields`).
+struct Catalogue {
+    name: &'static str,
+    json: JsonValue,
+}
+
+static CATALOGUES: LazyLock<Vec<Catalogue>> = LazyLock::new(|| {
+    LOCALES
+        .iter()
+        .map(|(name, text)| Catalogue {
+            name,
+            json: json::parse(text).unwrap(),
+        })
+        .collect()
+});
+// index into CATALOGUES, shared by every thread so the GUI's compiles see it too
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+
+/// Switches the language of all messages from now on. Takes a name like "es",
+/// or a system locale like "es_MX.UTF-8". Gives back false (and changes nothing)
+/// if there is no catalogue for it.
+pub fn set_locale(name: &str) -> bool {
+    let short = name.split(['_', '-', '.']).next().unwrap_or_default();
+    match CATALOGUES
+        .iter()
+        .position(|catalogue| catalogue.name.eq_ignore_ascii_case(short))
+    {
+        Some(ind) => {
+            CURRENT.store(ind, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+// looks in the current language first, then English, so a half translated file still works
+fn lookup(section: &str, key: &str, field: Option<&str>) -> Option<&'static str> {
+    let current = &CATALOGUES[CURRENT.load(Ordering::Relaxed)];
+    [current, &CATALOGUES[0]].into_iter().find_map(|catalogue| {
+        let val = &catalogue.json[section][key];
+        match field {
+            Some(field) => val[field].as_str(),
+            None => val.as_str(),
+        }
+    })
+}
+
+/// Gives the long explanation of an error code, like `explain("P0005")`,
+/// or None if there is no error with that code.
+pub fn explain(code: &str) -> Option<String> {
+    let code = code.trim().to_uppercase();
+    // there is no error to fill the fields in from, so they are left blank
+    let message = blank_fields(lookup("errors", &code, Some("message"))?);
+    let explanation = lookup("errors", &code, Some("explanation")).unwrap_or_default();
+    let mut ans = format!("{}: {}\n\n{}", code, message, explanation);
+    match lookup("errors", &code, Some("example")) {
+        Some(example) if !example.is_empty() => {
+            ans += &format!("\n\n{}\n{}", message_template("example"), example);
+        }
+        _ => (),
+    }
+    Some(ans)
+}
+
+/// The message for an error code, or one of the phrases used around messages
+/// and in the GUI (like "on_line" or "you_won").
+pub fn message_template(key: &str) -> &'static str {
+    lookup("errors", key, Some("message"))
+        .or_else(|| lookup("phrases", key, None))
+        .unwrap_or("???")
+}
+
+// puts "..." in every `{name}` spot of a template
+fn blank_fields(template: &str) -> String {
+    let mut ans = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{')
+        && let Some(len) = rest[start..].find('}')
+    {
+        ans += &rest[..start];
+        ans += "...";
+        rest = &rest[start + len + 1..];
+    }
+    ans + rest
+}
+
+// puts the fields into the `{name}` spots of a template
+pub fn fill(template: &str, fields: &[(&str, String)]) -> String {
+    let mut ans = template.to_owned();
+    for (name, val) in fields {
+        ans = ans.replace(&format!("{{{}}}", name), val);
+    }
+    ans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CATALOGUES, explain};
+
+    #[test]
+    fn explanations_have_no_fields_left() {
+        for (code, _) in CATALOGUES[0].json["errors"].entries() {
+            // only the message has fields, examples have braces of their own
+            let text = explain(code).unwrap();
+            let message = text.lines().next().unwrap();
+            assert!(!message.contains('{'), "{}", message);
+        }
+    }
+}
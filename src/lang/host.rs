@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use super::{builtins::is_builtin, errors::ErrorType, tokens::Type, wrapped_val::WrappedVal};
+
+/// The rust side of a host function. It gets the args in the order they were
+/// written, and an error message crashes the program. Void functions give back None.
+pub type HostFn = dyn Fn(Vec<WrappedVal>) -> Result<Option<WrappedVal>, String> + Send + Sync;
+
+#[derive(Clone)]
+pub struct HostFunction {
+    pub name: String,
+    pub params: Vec<Type>,
+    pub ret_type: Type,
+    func: Arc<HostFn>,
+}
+
+/// Functions that the app gives to Prism code, like `move_robot()` in a story level.
+/// Every stage of the compiler and the VM gets its own clone of it.
+#[derive(Clone, Default)]
+pub struct HostRegistry {
+    funcs: Vec<HostFunction>,
+}
+impl HostRegistry {
+    pub fn new() -> Self {
+        HostRegistry { funcs: Vec::new() }
+    }
+    /// Only ints, dcmls and bools can go in and out of host functions for now,
+    /// as strings and arrays live inside the VM.
+    // only embedders call these, the app itself has no host functions yet
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn register<F>(
+        &mut self,
+        name: &str,
+        params: Vec<Type>,
+        ret_type: Type,
+        func: F,
+    ) -> Result<(), String>
+    where
+        F: Fn(Vec<WrappedVal>) -> Result<WrappedVal, String> + Send + Sync + 'static,
+    {
+        if ret_type == Type::Void {
+            return Err(format!(
+                "Host function '{}' gives back nothing, use register_void for it",
+                name
+            ));
+        }
+        self.add(
+            name,
+            params,
+            ret_type,
+            Arc::new(move |args| func(args).map(Some)),
+        )
+    }
+    /// Same as `register`, for a function that only does something, like
+    /// `move_robot()`, and gives back nothing. It can only be called on its own line.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn register_void<F>(&mut self, name: &str, params: Vec<Type>, func: F) -> Result<(), String>
+    where
+        F: Fn(Vec<WrappedVal>) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.add(
+            name,
+            params,
+            Type::Void,
+            Arc::new(move |args| func(args).map(|()| None)),
+        )
+    }
+    fn add(
+        &mut self,
+        name: &str,
+        params: Vec<Type>,
+        ret_type: Type,
+        func: Arc<HostFn>,
+    ) -> Result<(), String> {
+        if is_builtin(name) {
+            return Err(format!("'{}' is already a builtin function", name));
+        }
+        if self.find(name).is_some() {
+            return Err(format!("'{}' is already registered", name));
+        }
+        for typ in params.iter().chain([&ret_type]) {
+            if !matches!(typ, Type::Int | Type::Dcml | Type::Bool | Type::Void) {
+                return Err(format!(
+                    "Host function '{}' uses type '{:#?}', only int, dcml and bool are allowed",
+                    name, typ
+                ));
+            }
+        }
+        if params.contains(&Type::Void) {
+            return Err(format!("Host function '{}' can't take a void", name));
+        }
+        self.funcs.push(HostFunction {
+            name: name.to_owned(),
+            params,
+            ret_type,
+            func,
+        });
+        Ok(())
+    }
+    /// Gives back the id that `CallHost` uses along with the function.
+    pub fn find(&self, name: &str) -> Option<(u16, &HostFunction)> {
+        self.funcs
+            .iter()
+            .enumerate()
+            .find(|(_, func)| func.name == name)
+            .map(|(ind, func)| (ind as u16, func))
+    }
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.funcs.iter().map(|func| func.name.as_str())
+    }
+    pub fn get(&self, id: u16) -> &HostFunction {
+        &self.funcs[id as usize]
+    }
+}
+impl HostFunction {
+    /// Gives back None for a void function.
+    pub fn call(&self, args: Vec<WrappedVal>) -> Result<Option<WrappedVal>, ErrorType> {
+        let ans = (self.func)(args).map_err(|reason| ErrorType::HostFailed {
+            name: self.name.to_owned(),
+            reason,
+        })?;
+        if let Some(ref val) = ans
+            && val.type_enum() != self.ret_type
+        {
+            return Err(ErrorType::HostWrongType {
+                name: self.name.to_owned(),
+                expected: self.ret_type.to_owned(),
+                found: val.type_enum(),
+            });
+        }
+        Ok(ans)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::HostRegistry;
+    use crate::lang::{
+        DEFAULT_SEED, keywords::ENGLISH, run_code_with_host, tokens::Type, wrapped_val::WrappedVal,
+    };
+
+    // a move_robot() that writes down every move
+    fn robot() -> (HostRegistry, Arc<Mutex<Vec<i32>>>) {
+        let moves = Arc::new(Mutex::new(Vec::new()));
+        let mut host = HostRegistry::new();
+        let log = Arc::clone(&moves);
+        host.register_void("move_robot", vec![Type::Int], move |args| {
+            let WrappedVal::Int(steps) = args[0] else {
+                unreachable!()
+            };
+            log.lock().unwrap().push(steps);
+            Ok(())
+        })
+        .unwrap();
+        (host, moves)
+    }
+
+    #[test]
+    fn void_function_runs_on_its_own_line() {
+        let (host, moves) = robot();
+        let code = "fun main() -> int {
+    int i = 1;
+    while i < 4 {
+        move_robot(i);
+        i = i + 1;
+    }
+    return i;
+}";
+        let res = run_code_with_host(code.to_owned(), &ENGLISH, DEFAULT_SEED, host);
+        assert_eq!(res.map(|(val, _)| val).ok(), Some(4));
+        assert_eq!(*moves.lock().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn void_function_has_no_value() {
+        let (host, _) = robot();
+        let code = "fun main() -> int {
+    var moved = move_robot(3);
+    int also = move_robot(3);
+    return 0;
+}";
+        let codes = match run_code_with_host(code.to_owned(), &ENGLISH, DEFAULT_SEED, host) {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors.iter().map(|err| err.e_type.code()).collect(),
+        };
+        assert_eq!(codes, vec!["T0014", "T0006"]);
+    }
+
+    #[test]
+    fn void_needs_register_void() {
+        let mut host = HostRegistry::new();
+        let res = host.register("beep", vec![], Type::Void, |_| Ok(WrappedVal::Int(0)));
+        assert!(res.is_err());
+        assert!(host.register_void("beep", vec![], |_| Ok(())).is_ok());
+    }
+}
@@ -0,0 +1,151 @@
+use super::{
+    ast::Loc,
+    errors::{CompileError, ErrorType},
+    lexer::LexEngine,
+    tokens::{Literal, Token},
+};
+
+/// The words the lexer turns into keywords. They are in the same order as
+/// `LexEngine::KEYWORDS`, so every set makes the same `Token`s and the only
+/// difference is how the code looks.
+pub struct KeywordSet {
+    pub name: &'static str,
+    pub words: [&'static str; 14],
+}
+
+pub const ENGLISH: KeywordSet = KeywordSet {
+    name: "en",
+    words: LexEngine::KEYWORDS,
+};
+
+pub const SPANISH: KeywordSet = KeywordSet {
+    name: "es",
+    words: [
+        "funcion",
+        "const",
+        "entero",
+        "texto",
+        "decimal",
+        "bool",
+        "var",
+        "si",
+        "sino",
+        "mientras",
+        "verdadero",
+        "falso",
+        "devolver",
+        "convertir",
+    ],
+};
+
+pub const KEYWORD_SETS: [&KeywordSet; 2] = [&ENGLISH, &SPANISH];
+
+/// Finds a keyword set by its name, like "es".
+pub fn keyword_set(name: &str) -> Option<&'static KeywordSet> {
+    KEYWORD_SETS.into_iter().find(|set| set.name == name)
+}
+
+// misspelled keywords get suggestions from every set, since the parser
+// doesn't know which one the code was written in
+pub fn all_keywords<'a>() -> impl Iterator<Item = &'a str> {
+    KEYWORD_SETS
+        .into_iter()
+        .flat_map(|set| set.words)
+        .map(|word| -> &'a str { word })
+}
+
+/// Rewrites code from one keyword set to another, like `si x > 0 { ... }` to
+/// `if x > 0 { ... }`. Everything else (names, spacing, comments, strings)
+/// is left how it was. Names that would turn into keywords are errors.
+pub fn translate(
+    code: &str,
+    from: &'static KeywordSet,
+    to: &'static KeywordSet,
+) -> Result<String, Vec<CompileError>> {
+    let toks = LexEngine::with_keywords(code.to_owned(), from).lex_all()?;
+    let mut lines = code
+        .split('\n')
+        .map(|line| line.chars().collect::<Vec<char>>())
+        .collect::<Vec<Vec<char>>>();
+    let mut errors = Vec::new();
+    // going from the end, so changing the length of a word doesn't move the ones before it
+    for (tok, loc) in toks.iter().rev() {
+        match tok {
+            Token::Ident(name) if to.words.contains(&name.as_str()) => {
+                errors.push(CompileError::at(
+                    ErrorType::KeywordClash {
+                        name: name.to_owned(),
+                    },
+                    loc,
+                ));
+            }
+            Token::Fun
+            | Token::Const
+            | Token::DeclareType(_)
+            | Token::Var
+            | Token::If
+            | Token::Else
+            | Token::While
+            | Token::Lit(Literal::Bool(_))
+            | Token::Return
+            | Token::Cast => replace_word(&mut lines, loc, from, to),
+            _ => (),
+        }
+    }
+    if !errors.is_empty() {
+        errors.reverse();
+        return Err(errors);
+    }
+    Ok(lines
+        .into_iter()
+        .map(|line| line.into_iter().collect::<String>())
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+fn replace_word(lines: &mut [Vec<char>], loc: &Loc, from: &KeywordSet, to: &KeywordSet) {
+    let line = &mut lines[loc.line as usize - 1];
+    let (start, end) = (loc.col as usize - 1, loc.end_col as usize - 1);
+    let word = line[start..end].iter().collect::<String>();
+    let ind = from
+        .words
+        .iter()
+        .position(|keyword| *keyword == word)
+        .unwrap();
+    line.splice(start..end, to.words[ind].chars());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ENGLISH, SPANISH, translate};
+    use crate::lang::{DEFAULT_SEED, run_code};
+
+    const CODE: &str = "funcion doble(entero x) -> entero {
+    si verdadero { devolver x * 2; }
+    devolver 0;
+}
+funcion main() -> entero {
+    entero total = 0;
+    mientras total < 10 { total = total + doble(3); }
+    devolver total;
+}";
+
+    #[test]
+    fn runs_in_the_selected_set() {
+        assert_eq!(
+            run_code(CODE.to_owned(), &SPANISH, DEFAULT_SEED).unwrap().0,
+            12
+        );
+        let codes = match run_code(CODE.to_owned(), &ENGLISH, DEFAULT_SEED) {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors.iter().map(|err| err.e_type.code()).collect(),
+        };
+        assert!(!codes.is_empty());
+    }
+
+    #[test]
+    fn translated_code_runs_the_same() {
+        let english = translate(CODE, &SPANISH, &ENGLISH).unwrap();
+        assert_eq!(run_code(english, &ENGLISH, DEFAULT_SEED).unwrap().0, 12);
+    }
+}
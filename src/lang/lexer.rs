@@ -5,7 +5,10 @@ use crate::lang::{
     tokens::{Literal, Operator, ShortHand, Token, Type},
 };
 
-use super::ast::Loc;
+use super::{
+    ast::Loc,
+    keywords::{ENGLISH, KeywordSet},
+};
 
 pub struct LexEngine {
     line: u32,
@@ -13,9 +16,21 @@ pub struct LexEngine {
     cur_char: char,
     char_collect: VecDeque<char>,
     finished: bool,
+    // where the token being lexed started
+    tok_start: (u32, u32),
+    keywords: &'static KeywordSet,
 }
 impl LexEngine {
+    // every word the lexer turns into something other than an ident
+    pub const KEYWORDS: [&str; 14] = [
+        "fun", "const", "int", "string", "dcml", "bool", "var", "if", "else", "while", "true",
+        "false", "return", "become",
+    ];
     pub fn new(langstr: String) -> Self {
+        LexEngine::with_keywords(langstr, &ENGLISH)
+    }
+    /// Lexes code written with another set of keywords, like `si` instead of `if`.
+    pub fn with_keywords(langstr: String, keywords: &'static KeywordSet) -> Self {
         let mut char_vec = langstr.chars().collect::<VecDeque<char>>();
         let first_char = match char_vec.pop_front() {
             Some(x) => x,
@@ -27,6 +42,8 @@ impl LexEngine {
             cur_char: first_char,
             char_collect: char_vec,
             finished: false,
+            tok_start: (1, 1),
+            keywords,
         }
     }
     pub fn append_string(&mut self, new_stuff: String) {
@@ -35,45 +52,44 @@ impl LexEngine {
         }
         self.finished = false;
     }
-    pub fn lex_all(mut self) -> Result<Vec<(Token, Loc)>, CompileError> {
+    /// Lexes everything, skipping past the bad part after an error
+    /// so every error in the code is reported at once.
+    pub fn lex_all(mut self) -> Result<Vec<(Token, Loc)>, Vec<CompileError>> {
         let mut all_of_it = Vec::new();
+        let mut errors = Vec::new();
         loop {
-            let tok = self.get_tok()?;
-            // println!("{:#?}", tok);
-            if let Token::EndOfFile = tok.0 {
-                break;
+            match self.get_tok() {
+                Ok((Token::EndOfFile, _)) => break,
+                Ok(tok) => all_of_it.push(tok),
+                Err(e) => {
+                    errors.push(e);
+                    self.skip_bad_chars();
+                }
             }
-            all_of_it.push(tok);
         }
-        Ok(all_of_it)
+        if errors.is_empty() {
+            Ok(all_of_it)
+        } else {
+            Err(errors)
+        }
     }
     pub fn get_tok(&mut self) -> Result<(Token, Loc), CompileError> {
-        // remove whitespace
-        if self.finished {
-            return Ok((Token::EndOfFile, Loc::new(self.line, self.col)));
-        }
-        while self.cur_char.is_whitespace() {
-            self.eat_char();
+        // remove whitespace and comments
+        loop {
             if self.finished {
                 return Ok((Token::EndOfFile, Loc::new(self.line, self.col)));
             }
-        }
-        // remove comments
-        if self.cur_char == '#' {
-            while self.cur_char != '\n' {
+            if self.cur_char.is_whitespace() {
                 self.eat_char();
-                if self.finished {
-                    return Ok((
-                        Token::EndOfFile,
-                        Loc {
-                            line: self.line,
-                            col: self.col,
-                        },
-                    ));
+            } else if self.cur_char == '#' {
+                while self.cur_char != '\n' && !self.finished {
+                    self.eat_char();
                 }
+            } else {
+                break;
             }
-            self.eat_char();
         }
+        self.tok_start = (self.line, self.col);
         // find ident
         if self.is_alpha(false) {
             let mut ident_string = String::new();
@@ -83,29 +99,40 @@ impl LexEngine {
                 ident_string.push(self.cur_char);
                 self.eat_char();
             }
+            // the keyword set is in the same order as KEYWORDS
+            let keyword = self
+                .keywords
+                .words
+                .iter()
+                .position(|word| *word == ident_string)
+                .map(|ind| LexEngine::KEYWORDS[ind]);
             return Ok((
-                match ident_string.as_str() {
-                    "fun" => Token::Fun,
-                    "int" => Token::DeclareType(Type::Int),
-                    "string" => Token::DeclareType(Type::String),
-                    "dcml" => Token::DeclareType(Type::Dcml),
-                    "bool" => Token::DeclareType(Type::Bool),
-                    "if" => Token::If,
-                    "else" => Token::Else,
-                    "while" => Token::While,
-                    "true" => Token::Lit(Literal::Bool(true)),
-                    "false" => Token::Lit(Literal::Bool(false)),
-                    "return" => Token::Return,
-                    "become" => Token::Cast,
-                    x => Token::Ident(x.to_owned()),
-                },
-                Loc {
-                    line: self.line,
-                    col: self.col,
+                match keyword {
+                    Some("fun") => Token::Fun,
+                    Some("const") => Token::Const,
+                    Some("int") => Token::DeclareType(Type::Int),
+                    Some("string") => Token::DeclareType(Type::String),
+                    Some("dcml") => Token::DeclareType(Type::Dcml),
+                    Some("bool") => Token::DeclareType(Type::Bool),
+                    Some("var") => Token::Var,
+                    Some("if") => Token::If,
+                    Some("else") => Token::Else,
+                    Some("while") => Token::While,
+                    Some("true") => Token::Lit(Literal::Bool(true)),
+                    Some("false") => Token::Lit(Literal::Bool(false)),
+                    Some("return") => Token::Return,
+                    Some("become") => Token::Cast,
+                    _ => Token::Ident(ident_string),
                 },
+                self.tok_loc(),
             ));
         }
-        if self.is_numeric() {
+        // a '.' before a letter is a dot op like `.len`, not the start of a number
+        let starts_dot_op = self.cur_char == '.'
+            && self
+                .peek_char()
+                .is_some_and(|next| next.is_ascii_alphabetic() || *next == '_');
+        if self.is_numeric() && !starts_dot_op {
             let mut has_point = false;
             let mut num_string = String::new();
             if self.cur_char == '.' {
@@ -117,7 +144,7 @@ impl LexEngine {
             while self.is_numeric() {
                 if self.cur_char == '.' {
                     if has_point {
-                        return Err(self.err("Can't have two points in a number.".to_owned()));
+                        return Err(self.err(ErrorType::TwoPointsInNumber));
                     } else {
                         has_point = true;
                     }
@@ -127,24 +154,12 @@ impl LexEngine {
             }
             if has_point {
                 return match num_string.parse::<f64>() {
-                    Ok(x) => Ok((
-                        Token::Lit(Literal::Dcml(x)),
-                        Loc {
-                            line: self.line,
-                            col: self.col,
-                        },
-                    )),
+                    Ok(x) => Ok((Token::Lit(Literal::Dcml(x)), self.tok_loc())),
                     Err(_) => unreachable!(),
                 };
             } else {
                 return match num_string.parse::<i32>() {
-                    Ok(x) => Ok((
-                        Token::Lit(Literal::Int(x)),
-                        Loc {
-                            line: self.line,
-                            col: self.col,
-                        },
-                    )),
+                    Ok(x) => Ok((Token::Lit(Literal::Int(x)), self.tok_loc())),
                     Err(_) => unreachable!(),
                 };
             }
@@ -152,6 +167,9 @@ impl LexEngine {
         if self.cur_char == '"' {
             self.eat_char();
             let mut string_lit = String::new();
+            // a bad backslash is reported after the whole string is eaten,
+            // so the rest of the string isn't lexed as code
+            let mut bad_escape = None;
             // let mut last_char = '"';
             while self.cur_char != '"' && !self.finished
             /* || (self.cur_char == '"' && last_char == '\\') */
@@ -159,20 +177,19 @@ impl LexEngine {
                 if self.cur_char == '\\' {
                     println!("No way, hey");
                     let next_char = match self.peek_char() {
-                        Some(x) => x,
+                        Some(x) => *x,
                         None => {
-                            return Err(self.err("Ended right after that backslash".to_owned()));
+                            return Err(self.err(ErrorType::BackslashAtEnd));
                         }
                     };
                     match next_char {
                         '\\' => string_lit.push('\\'),
                         'n' => string_lit.push('\n'),
                         '"' => string_lit.push('"'),
-                        _ => {
-                            self.eat_char();
-                            return Err(
-                                self.err("The backslash didn't have the correct stuff".to_owned())
-                            );
+                        escape => {
+                            if bad_escape.is_none() {
+                                bad_escape = Some(self.err(ErrorType::BadEscape { escape }));
+                            }
                         }
                     }
                     self.eat_char();
@@ -184,16 +201,13 @@ impl LexEngine {
                 }
             }
             if self.finished {
-                return Err(self.err("You can't stop in the middle of a string!".to_owned()));
+                return Err(self.err(ErrorType::UnfinishedString));
             }
             self.eat_char();
-            return Ok((
-                Token::Lit(Literal::String(string_lit)),
-                Loc {
-                    line: self.line,
-                    col: self.col,
-                },
-            ));
+            if let Some(e) = bad_escape {
+                return Err(e);
+            }
+            return Ok((Token::Lit(Literal::String(string_lit)), self.tok_loc()));
         }
         if self.is_part_of_symbol() {
             let mut sym_string = String::new();
@@ -206,16 +220,10 @@ impl LexEngine {
             }
             match LexEngine::get_symbol(&sym_string) {
                 Some(x) => {
-                    return Ok((
-                        x,
-                        Loc {
-                            line: self.line,
-                            col: self.col,
-                        },
-                    ));
+                    return Ok((x, self.tok_loc()));
                 }
                 None => {
-                    return Err(self.err("I have no clue what that symbol is".to_owned()));
+                    return Err(self.err(ErrorType::UnknownSymbol { symbol: sym_string }));
                 }
             }
         }
@@ -230,9 +238,9 @@ impl LexEngine {
                     ')' => Token::RightParen,
                     ']' => Token::RightBrack,
                     '}' => Token::RightCurly,
-                    _ => return Err(self.err("Weird grouping stuff".to_string())),
+                    found => return Err(self.err(ErrorType::UnknownChar { found })),
                 },
-                Loc::new(self.line, self.col),
+                self.tok_loc(),
             ));
         }
         if self.cur_char == '.' {
@@ -242,14 +250,25 @@ impl LexEngine {
                 dot_str.push(self.cur_char);
                 self.eat_char();
             }
-            return Ok((Token::Dot(dot_str), Loc::new(self.line, self.col)));
+            return Ok((Token::Dot(dot_str), self.tok_loc()));
         }
         eprintln!(
             "Did not know what to do with {}. I got no clue ngl",
             self.cur_char
         );
+        let found = self.cur_char;
         self.eat_char();
-        return Err(self.err("What is even that char doing?".to_owned()));
+        return Err(self.err(ErrorType::UnknownChar { found }));
+    }
+    // eats the rest of whatever caused an error, up to the next space, ';' or bracket
+    fn skip_bad_chars(&mut self) {
+        while !self.finished
+            && !self.cur_char.is_whitespace()
+            && self.cur_char != ';'
+            && !self.is_grouper()
+        {
+            self.eat_char();
+        }
     }
     fn eat_char(&mut self) {
         if self.cur_char == '\n' {
@@ -277,6 +296,7 @@ impl LexEngine {
     fn is_alpha(&self, numeric: bool) -> bool {
         if (self.cur_char >= 'a' && self.cur_char <= 'z')
             || (self.cur_char >= 'A' && self.cur_char <= 'Z')
+            || self.cur_char == '_'
             || (numeric && self.cur_char >= '0' && self.cur_char <= '9')
         {
             true
@@ -308,12 +328,17 @@ impl LexEngine {
             _ => false,
         }
     }
-    fn err(&self, err_name: String) -> CompileError {
-        return CompileError {
-            e_type: ErrorType::LexingError(err_name),
-            line: self.line,
-            col: self.col,
-        };
+    // from the start of the token to where the lexer is now
+    fn tok_loc(&self) -> Loc {
+        Loc {
+            line: self.tok_start.0,
+            col: self.tok_start.1,
+            end_line: self.line,
+            end_col: self.col,
+        }
+    }
+    fn err(&self, e_type: ErrorType) -> CompileError {
+        return CompileError::at(e_type, &self.tok_loc());
     }
     fn get_symbol(sym_str: &str) -> Option<Token> {
         match sym_str {
@@ -334,6 +359,9 @@ impl LexEngine {
             "&" => Some(Token::Op(Operator::BAnd)),
             "|" => Some(Token::Op(Operator::BOr)),
             "^" => Some(Token::Op(Operator::BXor)),
+            "<<" => Some(Token::Op(Operator::Shl)),
+            ">>" => Some(Token::Op(Operator::Shr)),
+            "**" => Some(Token::Op(Operator::Pow)),
             "=" => Some(Token::Assign),
             "+=" => Some(Token::ShortHand(ShortHand::AddEq)),
             "-=" => Some(Token::ShortHand(ShortHand::SubEq)),
@@ -0,0 +1,326 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    ast::{DotOp, ExprAST, FunctionAst, GlobalConst, Loc, Statement, WhileBlock},
+    callgraph::CallGraph,
+    consteval::{eval_const_expr, eval_global_consts},
+    errors::{CompileError, Diagnostic, ErrorType},
+    tokens::Literal,
+};
+
+/// Looks for code that runs fine but probably isn't what was meant, like variables
+/// that are never used. It expects code that passed the type check, so every name
+/// it sees exists. Names starting with _ are never warned about.
+pub fn lint(ast: &[FunctionAst], global_consts: &[GlobalConst]) -> Vec<Diagnostic> {
+    let consts = eval_global_consts(global_consts);
+    let mut warnings = Vec::new();
+    for func in ast {
+        let mut linter = FuncLinter {
+            func,
+            consts: &consts,
+            scopes: Vec::new(),
+            warnings: Vec::new(),
+        };
+        linter.lint_func();
+        warnings.append(&mut linter.warnings);
+    }
+    warnings.append(&mut unused_functions(ast));
+    warnings.sort_by_key(|warning| (warning.line, warning.col));
+    warnings
+}
+
+struct Local {
+    name: String,
+    loc: Loc,
+    is_param: bool,
+    used: bool,
+}
+
+struct FuncLinter<'a> {
+    func: &'a FunctionAst,
+    consts: &'a HashMap<String, Literal>,
+    // the innermost block is last
+    scopes: Vec<Vec<Local>>,
+    warnings: Vec<Diagnostic>,
+}
+impl FuncLinter<'_> {
+    fn lint_func(&mut self) {
+        let params = self
+            .func
+            .params
+            .iter()
+            .map(|(name, _)| Local {
+                name: name.to_owned(),
+                loc: self.func.loc,
+                is_param: true,
+                used: false,
+            })
+            .collect();
+        // the params and the body are in the same scope, like in the type checker
+        self.scopes.push(params);
+        for statement in &self.func.code {
+            self.lint_statement(statement);
+        }
+        self.pop_scope();
+    }
+    fn lint_block(&mut self, block: &[Statement]) {
+        self.scopes.push(Vec::new());
+        for statement in block {
+            self.lint_statement(statement);
+        }
+        self.pop_scope();
+    }
+    fn lint_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Expr(expr) => self.use_expr(&expr.expr),
+            Statement::Decl(decl) => {
+                self.use_expr(&decl.val);
+                let len = self.scopes.len();
+                self.scopes[len - 1].push(Local {
+                    name: decl.ident.to_owned(),
+                    loc: decl.ident_loc,
+                    is_param: false,
+                    used: false,
+                });
+            }
+            Statement::Assign(assign) => {
+                if let ExprAST::Var(name, _) = &assign.val
+                    && *name == assign.ident
+                {
+                    self.warn(
+                        &Loc::span(assign.ident_loc, assign.val_loc),
+                        ErrorType::SelfAssign {
+                            name: assign.ident.to_owned(),
+                        },
+                    );
+                }
+                // setting a variable isn't using it, only reading it is
+                self.use_expr(&assign.val);
+            }
+            Statement::If(ifblock) => {
+                self.use_expr(&ifblock.cond.expr);
+                self.lint_block(&ifblock.tcode);
+                self.lint_block(&ifblock.ecode);
+            }
+            Statement::While(whileblock) => {
+                self.use_expr(&whileblock.cond.expr);
+                self.check_loop(whileblock);
+                self.lint_block(&whileblock.code);
+            }
+            Statement::Return(ret) => self.use_expr(&ret.expr.expr),
+        }
+    }
+    // A loop can only stop if something inside it changes a variable in the condition.
+    // Anything that can't be checked, like calling a function (which could change
+    // arrays) or returning, is trusted to stop it.
+    fn check_loop(&mut self, whileblock: &WhileBlock) {
+        // `while false` never starts, the unreachable code warning covers it
+        if let Some(Literal::Bool(false)) = eval_const_expr(&whileblock.cond.expr, self.consts) {
+            return;
+        }
+        let mut reads = HashSet::new();
+        let mut changed = HashSet::new();
+        if !cond_reads(&whileblock.cond.expr, &mut reads)
+            || !block_changes(&whileblock.code, &mut changed)
+        {
+            return;
+        }
+        if reads.is_disjoint(&changed) {
+            self.warn(&whileblock.loc, ErrorType::InfiniteLoop);
+        }
+    }
+    fn use_expr(&mut self, expr: &ExprAST) {
+        match expr {
+            ExprAST::Var(name, _) => {
+                // names that aren't found are global constants
+                if let Some(local) = self
+                    .scopes
+                    .iter_mut()
+                    .rev()
+                    .flat_map(|scope| scope.iter_mut())
+                    .find(|local| local.name == *name)
+                {
+                    local.used = true;
+                }
+            }
+            ExprAST::Lit(_) => (),
+            ExprAST::BinOp(_, lhs, rhs) | ExprAST::Indexed(lhs, rhs) => {
+                self.use_expr(lhs);
+                self.use_expr(rhs);
+            }
+            ExprAST::Call(_, args, _) => {
+                for arg in args {
+                    self.use_expr(&arg.expr);
+                }
+            }
+            ExprAST::Casted(_, expr) => self.use_expr(expr),
+            ExprAST::DotOp(op, expr) => {
+                self.use_expr(expr);
+                if let DotOp::Push(val) = op {
+                    self.use_expr(val);
+                }
+            }
+        }
+    }
+    fn pop_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+        for local in scope {
+            if local.used || local.name.starts_with('_') {
+                continue;
+            }
+            let e_type = if local.is_param {
+                ErrorType::UnusedParam {
+                    func: self.func.name.to_owned(),
+                    name: local.name,
+                }
+            } else {
+                ErrorType::UnusedVariable { name: local.name }
+            };
+            self.warn(&local.loc, e_type);
+        }
+    }
+    fn warn(&mut self, loc: &Loc, e_type: ErrorType) {
+        self.warnings.push(CompileError::at(e_type, loc));
+    }
+}
+
+// false if the condition could change something itself
+fn cond_reads(expr: &ExprAST, reads: &mut HashSet<String>) -> bool {
+    match expr {
+        ExprAST::Var(name, _) => {
+            reads.insert(name.to_owned());
+            true
+        }
+        ExprAST::Lit(_) => true,
+        ExprAST::BinOp(_, lhs, rhs) | ExprAST::Indexed(lhs, rhs) => {
+            cond_reads(lhs, reads) && cond_reads(rhs, reads)
+        }
+        ExprAST::Casted(_, expr) | ExprAST::DotOp(DotOp::Len, expr) => cond_reads(expr, reads),
+        ExprAST::Call(_, _, _) | ExprAST::DotOp(DotOp::Push(_) | DotOp::Pop, _) => false,
+    }
+}
+
+// collects the variables the block sets (or pushes to and pops from),
+// false if it calls a function or returns
+fn block_changes(block: &[Statement], changed: &mut HashSet<String>) -> bool {
+    block.iter().all(|statement| match statement {
+        Statement::Expr(expr) => expr_changes(&expr.expr, changed),
+        Statement::Decl(decl) => expr_changes(&decl.val, changed),
+        Statement::Assign(assign) => {
+            changed.insert(assign.ident.to_owned());
+            expr_changes(&assign.val, changed)
+        }
+        Statement::If(ifblock) => {
+            expr_changes(&ifblock.cond.expr, changed)
+                && block_changes(&ifblock.tcode, changed)
+                && block_changes(&ifblock.ecode, changed)
+        }
+        Statement::While(whileblock) => {
+            expr_changes(&whileblock.cond.expr, changed) && block_changes(&whileblock.code, changed)
+        }
+        Statement::Return(_) => false,
+    })
+}
+
+fn expr_changes(expr: &ExprAST, changed: &mut HashSet<String>) -> bool {
+    match expr {
+        ExprAST::Var(_, _) | ExprAST::Lit(_) => true,
+        ExprAST::BinOp(_, lhs, rhs) | ExprAST::Indexed(lhs, rhs) => {
+            expr_changes(lhs, changed) && expr_changes(rhs, changed)
+        }
+        ExprAST::Casted(_, expr) => expr_changes(expr, changed),
+        ExprAST::DotOp(op, expr) => {
+            if let (DotOp::Push(_) | DotOp::Pop, ExprAST::Var(name, _)) = (op, expr.as_ref()) {
+                changed.insert(name.to_owned());
+            }
+            let pushed = match op {
+                DotOp::Push(val) => expr_changes(val, changed),
+                DotOp::Len | DotOp::Pop => true,
+            };
+            pushed && expr_changes(expr, changed)
+        }
+        ExprAST::Call(_, _, _) => false,
+    }
+}
+
+// every function that main can't reach through calls never runs
+fn unused_functions(ast: &[FunctionAst]) -> Vec<Diagnostic> {
+    let graph = CallGraph::new(ast);
+    let reached = graph.reachable_from("main");
+    if reached.is_empty() {
+        return Vec::new();
+    }
+    ast.iter()
+        .filter(|func| !reached.contains(func.name.as_str()) && !func.name.starts_with('_'))
+        .map(|func| {
+            CompileError::at(
+                ErrorType::UnusedFunction {
+                    name: func.name.to_owned(),
+                },
+                &func.loc,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lint;
+    use crate::lang::{keywords::ENGLISH, lexer::LexEngine, parser::ParsingMachine};
+
+    // only lints, the code never runs, so it can't get stuck in a loop
+    fn warning_codes(code: &str) -> Vec<&'static str> {
+        let toks = LexEngine::with_keywords(code.to_owned(), &ENGLISH)
+            .lex_all()
+            .unwrap();
+        let (ast, global_consts, errors) = ParsingMachine::new(toks).parse_all();
+        assert!(errors.is_empty(), "{:?}", errors);
+        lint(&ast, &global_consts)
+            .iter()
+            .map(|warning| warning.e_type.code())
+            .collect()
+    }
+
+    #[test]
+    fn unused_names() {
+        let code = "fun helper(int x) -> int { return 1; }
+fun quiet(int _x) -> int { int _y = 2; return 1; }
+fun main() -> int {
+    int unused = 5;
+    return quiet(1);
+}";
+        assert_eq!(warning_codes(code), vec!["W0002", "W0003", "W0001"]);
+    }
+
+    #[test]
+    fn used_names() {
+        let code = "fun double(int x) -> int { return x * 2; }
+fun main() -> int {
+    int y = 5;
+    return double(y);
+}";
+        assert!(warning_codes(code).is_empty());
+    }
+
+    #[test]
+    fn loop_that_never_changes_its_condition() {
+        let code = "fun main() -> int {
+    int i = 0;
+    int total = 0;
+    while i < 10 { total = total + 1; }
+    return total;
+}";
+        assert_eq!(warning_codes(code), vec!["W0007"]);
+    }
+
+    #[test]
+    fn loops_that_can_stop() {
+        let code = "fun main() -> int {
+    int i = 0;
+    while i < 10 { i = i + 1; }
+    while true { return i; }
+    return 0;
+}";
+        assert!(warning_codes(code).is_empty());
+    }
+}
@@ -1,34 +1,50 @@
 use std::{fs, time::Duration};
 
-use asm::{Assembler, print_instructions};
-use ast::{ExprAST, Expression, FunctionAst, IfBlock, Loc, Statement};
+use asm::{Assembler, listing, print_instructions};
+use ast::{ExprAST, Expression, FunctionAst, GlobalConst, IfBlock, Loc, Statement};
+use builtins::Rng;
+use callgraph::CallGraph;
 use codegen::{CompilerComposer, FuncCompiler};
 use ctrlflow::check_for_returns;
-use errors::CompileError;
+use errors::{CompileError, Diagnostic, ErrorType};
+use host::HostRegistry;
+use keywords::KeywordSet;
 use lexer::LexEngine;
+use lints::lint;
+use optimizing::optimize;
 use parser::ParsingMachine;
+use peephole::peephole;
 use tokens::{Literal, Type};
 use typecheck::TypeChecker;
 use vm::VM;
 
 // frontend
 mod ast;
+pub mod keywords;
 mod lexer;
 mod parser;
 pub mod tokens;
 // backend
 mod asm;
+mod builtins;
 mod bytecode;
 mod codegen;
+pub mod host;
 mod vm;
-mod wrapped_val;
+pub mod wrapped_val;
 // optimize
-// mod optimizing;
+mod optimizing;
+mod peephole;
 // error handling
 pub mod errors;
+pub mod explain;
 // semantic analysis
 mod array;
+pub mod callgraph;
+mod consteval;
 mod ctrlflow;
+mod lints;
+mod suggest;
 mod typecheck;
 // debugging
 mod repl;
@@ -51,33 +67,60 @@ pub fn run_lang_test(args: Vec<String>) {
         println!("You put this: '{}'", debug_or_eval);
         return;
     }
+    // `opt` after the path shows the instructions from before optimizing too
+    let show_unoptimized = args.get(4).is_some_and(|arg| arg == "opt");
     println!("Getting path");
     let path = args[3].clone();
     println!("Got path: {}", path);
     let raw = fs::read_to_string(path).expect("File not found");
     // println!("RAW CODE:\n{}", raw);
     let lex = LexEngine::new(raw);
-    let toks = lex.lex_all().unwrap();
+    let toks = match lex.lex_all() {
+        Ok(tokens) => tokens,
+        Err(errvec) => {
+            println!("Lexer reports ERRORS.\n{:#?}", errvec);
+            return;
+        }
+    };
     // println!("TOKENS:\n{:#?}", toks);
     let parser = ParsingMachine::new(toks);
-    let ast = parser.parse_all().unwrap();
+    let (ast, global_consts, errvec) = parser.parse_all();
+    if !errvec.is_empty() {
+        println!("Parser reports ERRORS.\n{:#?}", errvec);
+        return;
+    }
     // println!("AST:\n{:#?}", ast);
-    match check_for_returns(ast.to_owned()) {
-        Ok(()) => println!("Control Flow diagram reports NO ERRORS!"),
+    let mut warnings = match check_for_returns(ast.to_owned(), &global_consts) {
+        Ok(warnings) => {
+            println!("Control Flow diagram reports NO ERRORS!");
+            warnings
+        }
         Err(errvec) => {
             println!("Control Flow diagram reports RETURN ERRORS.\n{:#?}", errvec);
             return;
         }
-    }
-    let type_checker = TypeChecker::new(ast.to_owned());
+    };
+    let type_checker = TypeChecker::new(ast.to_owned(), global_consts.to_owned());
     if let Err(vec) = type_checker.check_all() {
         println!("Type Check reports TYPE ERRORS.\n{:#?}", vec);
         return;
     } else {
         println!("Type Check reports NO ERRORS!");
     }
-    let compiler = CompilerComposer::new(ast);
-    let instructions = compiler.parallel_compile();
+    warnings.append(&mut lint(&ast, &global_consts));
+    if !warnings.is_empty() {
+        println!("Lints report WARNINGS.\n{:#?}", warnings);
+    }
+    if show_unoptimized {
+        // neither has the peephole pass, so each shows what one stage did
+        let (unoptimized, optimized) = optimizing_listings(&ast, &global_consts);
+        println!("BEFORE OPTIMIZING:\n{}", unoptimized);
+        println!("AFTER OPTIMIZING THE AST:\n{}", optimized);
+        println!("AFTER THE PEEPHOLE PASS:");
+    }
+    let ast = optimize(ast, &global_consts);
+    let compiler = CompilerComposer::new(ast, global_consts);
+    let instructions = peephole(compiler.parallel_compile());
     print_instructions(&instructions);
     let bytecode = Assembler::new(instructions).assemble();
     let (pool, consts) = compiler.extract_pool_and_consts();
@@ -90,89 +133,172 @@ pub fn run_lang_test(args: Vec<String>) {
     } else {
         end_val = virtual_machine.execute_order_66();
     }
-    println!("The end value was {}", end_val);
+    match end_val {
+        Ok(x) => println!("The end value was {}", x),
+        Err(e) => println!("The program crashed.\n{:#?}", e),
+    }
 }
 
-pub fn run_code(code: String) -> Result<i32, Vec<CompileError>> {
-    let mut errvec: Vec<CompileError> = Vec::new();
-    let lexer = LexEngine::new(code);
-    let toks = match lexer.lex_all() {
-        Ok(tokens) => tokens,
-        Err(e) => {
-            errvec.push(e);
-            return Err(errvec);
-        }
-    };
-    let parser = ParsingMachine::new(toks);
-    let ast = match parser.parse_all() {
-        Ok(a) => a,
-        Err(e) => {
-            errvec.push(e);
-            return Err(errvec);
-        }
-    };
-    match check_for_returns(ast.to_owned()) {
-        Ok(()) => (),
-        Err(mut e) => {
-            errvec.append(&mut e);
-        }
-    }
-    if errvec.len() > 0 {
+/// The instructions of the code before optimizing and after optimizing the AST,
+/// both without the peephole pass, so they can be shown side by side.
+// for the GUI, which doesn't show them yet
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn optimization_listings(
+    code: String,
+    keywords: &'static KeywordSet,
+) -> Result<(String, String), Vec<CompileError>> {
+    let (ast, global_consts, _) = check_code(code, keywords, HostRegistry::new())?;
+    Ok(optimizing_listings(&ast, &global_consts))
+}
+
+fn optimizing_listings(ast: &[FunctionAst], global_consts: &[GlobalConst]) -> (String, String) {
+    let compiler = CompilerComposer::new(ast.to_vec(), global_consts.to_vec());
+    let unoptimized = listing(&compiler.parallel_compile());
+    let optimized_ast = optimize(ast.to_vec(), global_consts);
+    let compiler = CompilerComposer::new(optimized_ast, global_consts.to_vec());
+    (unoptimized, listing(&compiler.parallel_compile()))
+}
+
+/// Which functions call which in the code. It only has to parse, so this works
+/// on code that doesn't have a main, like a solution for a level.
+pub fn call_graph(
+    code: String,
+    keywords: &'static KeywordSet,
+) -> Result<CallGraph, Vec<CompileError>> {
+    let toks = LexEngine::with_keywords(code, keywords).lex_all()?;
+    let (ast, _, errvec) = ParsingMachine::new(toks).parse_all();
+    if !errvec.is_empty() {
         return Err(errvec);
     }
-    match TypeChecker::new(ast.to_owned()).check_all() {
-        Ok(()) => (),
-        Err(mut e) => {
-            errvec.append(&mut e);
-        }
+    Ok(CallGraph::new(&ast))
+}
+
+/// The seed `random()` starts from, unless the code is run with another one.
+pub const DEFAULT_SEED: u64 = Rng::DEFAULT_SEED;
+
+/// Runs the code and gives back the end value, along with warnings about code that
+/// still ran but probably isn't what was meant. The code is written with the
+/// words in `keywords`, like `&keywords::ENGLISH`, and `random()` gives the same
+/// numbers every time for the same `seed`.
+pub fn run_code(
+    code: String,
+    keywords: &'static KeywordSet,
+    seed: u64,
+) -> Result<(i32, Vec<Diagnostic>), Vec<CompileError>> {
+    run_code_with_host(code, keywords, seed, HostRegistry::new())
+}
+
+/// Same as `run_code`, but the code can also call the functions in `host`.
+pub fn run_code_with_host(
+    code: String,
+    keywords: &'static KeywordSet,
+    seed: u64,
+    host: HostRegistry,
+) -> Result<(i32, Vec<Diagnostic>), Vec<CompileError>> {
+    let (mut virtual_machine, warnings) = compile_code(code, keywords, seed, host, true)?;
+    match virtual_machine.execute_order_66() {
+        Ok(x) => Ok((x, warnings)),
+        Err(e_type) => Err(vec![crash(e_type)]),
     }
-    if !ast
-        .iter()
-        .map(|func| func.name.clone())
-        .collect::<Vec<String>>()
-        .contains(&"main".to_string())
-    {
-        errvec.push(CompileError {
-            e_type: errors::ErrorType::ParsingError("No main func bro".to_string()),
-            line: 0,
-            col: 0,
-        });
+}
+
+/// Runs the code and gives back the end value and how many instructions ran,
+/// with or without the peephole pass, to see how much it saves.
+pub fn count_instructions(
+    code: String,
+    keywords: &'static KeywordSet,
+    peephole_pass: bool,
+) -> Result<(i32, u64), Vec<CompileError>> {
+    let (mut virtual_machine, _) = compile_code(
+        code,
+        keywords,
+        DEFAULT_SEED,
+        HostRegistry::new(),
+        peephole_pass,
+    )?;
+    match virtual_machine.execute_order_66() {
+        Ok(x) => Ok((x, virtual_machine.executed())),
+        Err(e_type) => Err(vec![crash(e_type)]),
     }
-    if errvec.len() > 0 {
-        return Err(errvec);
+}
+
+pub fn run_code_timed(
+    code: String,
+    keywords: &'static KeywordSet,
+    seed: u64,
+    timed: Duration,
+    interval: Duration,
+) -> Result<(Option<i32>, Vec<Diagnostic>), Vec<CompileError>> {
+    run_code_timed_with_host(code, keywords, seed, timed, interval, HostRegistry::new())
+}
+
+/// Same as `run_code_timed`, but the code can also call the functions in `host`.
+pub fn run_code_timed_with_host(
+    code: String,
+    keywords: &'static KeywordSet,
+    seed: u64,
+    timed: Duration,
+    interval: Duration,
+    host: HostRegistry,
+) -> Result<(Option<i32>, Vec<Diagnostic>), Vec<CompileError>> {
+    let (virtual_machine, warnings) = compile_code(code, keywords, seed, host, true)?;
+    match virtual_machine.timed_run(timed, interval) {
+        Some(Ok(x)) => Ok((Some(x), warnings)),
+        Some(Err(e_type)) => Err(vec![crash(e_type)]),
+        None => Ok((None, warnings)),
     }
+}
+
+// the bytecode has no source locations, so crashes point nowhere
+fn crash(e_type: ErrorType) -> Diagnostic {
+    CompileError::new(e_type, 0, 0)
+}
 
-    let compiler = CompilerComposer::new(ast);
-    let instructions = compiler.parallel_compile();
+// everything before running: checks the code, then compiles it into a VM that is ready to go
+fn compile_code(
+    code: String,
+    keywords: &'static KeywordSet,
+    seed: u64,
+    host: HostRegistry,
+    peephole_pass: bool,
+) -> Result<(VM, Vec<Diagnostic>), Vec<CompileError>> {
+    let (ast, global_consts, warnings) = check_code(code, keywords, host.clone())?;
+    let ast = optimize(ast, &global_consts);
+    let compiler = CompilerComposer::new_with_host(ast, global_consts, host.clone());
+    let mut instructions = compiler.parallel_compile();
+    if peephole_pass {
+        instructions = peephole(instructions);
+    }
     let bytecode = Assembler::new(instructions).assemble();
     let (pool, consts) = compiler.extract_pool_and_consts();
-    Ok(VM::new(pool, consts, bytecode).execute_order_66())
+    let mut virtual_machine = VM::new(pool, consts, bytecode);
+    virtual_machine.set_host(host);
+    virtual_machine.seed_random(seed);
+    Ok((virtual_machine, warnings))
 }
 
-pub fn run_code_timed(
+/// Everything before code generation: the checked code, and its warnings.
+fn check_code(
     code: String,
-    timed: Duration,
-    interval: Duration,
-) -> Result<Option<i32>, Vec<CompileError>> {
+    keywords: &'static KeywordSet,
+    host: HostRegistry,
+) -> Result<(Vec<FunctionAst>, Vec<GlobalConst>, Vec<Diagnostic>), Vec<CompileError>> {
     let mut errvec: Vec<CompileError> = Vec::new();
-    let lexer = LexEngine::new(code);
+    let lexer = LexEngine::with_keywords(code, keywords);
     let toks = match lexer.lex_all() {
         Ok(tokens) => tokens,
-        Err(e) => {
-            errvec.push(e);
+        Err(mut e) => {
+            errvec.append(&mut e);
             return Err(errvec);
         }
     };
     let parser = ParsingMachine::new(toks);
-    let ast = match parser.parse_all() {
-        Ok(a) => a,
-        Err(e) => {
-            errvec.push(e);
-            return Err(errvec);
-        }
-    };
-    match check_for_returns(ast.to_owned()) {
-        Ok(()) => (),
+    // the functions that parsed fine are still checked for missing returns
+    let (ast, global_consts, mut parse_errs) = parser.parse_all();
+    errvec.append(&mut parse_errs);
+    let mut warnings = Vec::new();
+    match check_for_returns(ast.to_owned(), &global_consts) {
+        Ok(mut w) => warnings.append(&mut w),
         Err(mut e) => {
             errvec.append(&mut e);
         }
@@ -180,7 +306,7 @@ pub fn run_code_timed(
     if errvec.len() > 0 {
         return Err(errvec);
     }
-    match TypeChecker::new(ast.to_owned()).check_all() {
+    match TypeChecker::new_with_host(ast.to_owned(), global_consts.to_owned(), host).check_all() {
         Ok(()) => (),
         Err(mut e) => {
             errvec.append(&mut e);
@@ -192,19 +318,52 @@ pub fn run_code_timed(
         .collect::<Vec<String>>()
         .contains(&"main".to_string())
     {
-        errvec.push(CompileError {
-            e_type: errors::ErrorType::ParsingError("No main func bro".to_string()),
-            line: 0,
-            col: 0,
-        });
+        errvec.push(CompileError::new(errors::ErrorType::NoMain, 0, 0));
     }
     if errvec.len() > 0 {
         return Err(errvec);
     }
+    warnings.append(&mut lint(&ast, &global_consts));
+    warnings.sort_by_key(|warning| (warning.line, warning.col));
+    Ok((ast, global_consts, warnings))
+}
 
-    let compiler = CompilerComposer::new(ast);
-    let instructions = compiler.parallel_compile();
-    let bytecode = Assembler::new(instructions).assemble();
-    let (pool, consts) = compiler.extract_pool_and_consts();
-    Ok(VM::new(pool, consts, bytecode).timed_run(timed, interval))
+#[cfg(test)]
+mod tests {
+    use super::{keywords::ENGLISH, optimization_listings};
+
+    #[test]
+    fn listings_show_the_folded_add() {
+        let code = "fun main() -> int {
+    int x = 2 + 3;
+    return x;
+}";
+        let (unoptimized, optimized) = optimization_listings(code.to_owned(), &ENGLISH).unwrap();
+        assert!(unoptimized.lines().any(|line| line.ends_with("-- add")));
+        assert!(!optimized.lines().any(|line| line.ends_with("-- add")));
+    }
+}
+
+/// Helpers for the tests of every stage, they all run English code with the default seed.
+#[cfg(test)]
+mod test_util {
+    use super::{DEFAULT_SEED, errors::CompileError, keywords::ENGLISH, run_code};
+
+    /// The end value of code that has to compile and run.
+    pub fn run(code: &str) -> i32 {
+        run_code(code.to_owned(), &ENGLISH, DEFAULT_SEED).unwrap().0
+    }
+
+    /// The errors from code that has to fail, while compiling or running.
+    pub fn errors(code: &str) -> Vec<CompileError> {
+        run_code(code.to_owned(), &ENGLISH, DEFAULT_SEED).unwrap_err()
+    }
+
+    /// The codes of the errors if the code failed, of the warnings if it didn't.
+    pub fn codes(code: &str) -> Vec<&'static str> {
+        match run_code(code.to_owned(), &ENGLISH, DEFAULT_SEED) {
+            Ok((_, warnings)) => warnings.iter().map(|warn| warn.e_type.code()).collect(),
+            Err(errors) => errors.iter().map(|err| err.e_type.code()).collect(),
+        }
+    }
 }
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+
+use super::{
+    ast::{DotOp, ExprAST, Expression, FunctionAst, GlobalConst, Statement},
+    consteval::{eval_global_consts, fold_binop, fold_cast},
+    tokens::{Literal, Operator},
+};
+
+/// Makes the code do less work without changing what it does. Math on literals and
+/// constants is worked out now (`2 * SIZE` turns into `20`), things like `x * 1` and
+/// `x + 0` turn into just `x`, and ifs on `true` or `false` only keep the side that runs.
+/// It expects code that passed the type check. Anything that would crash when it runs,
+/// like an overflow, is left for the VM, and calls always stay in the same order.
+pub fn optimize(ast: Vec<FunctionAst>, global_consts: &[GlobalConst]) -> Vec<FunctionAst> {
+    let consts = eval_global_consts(global_consts);
+    ast.into_iter()
+        .map(|func| FunctionAst {
+            code: optimize_block(func.code, &consts),
+            ..func
+        })
+        .collect()
+}
+
+fn optimize_block(block: Vec<Statement>, consts: &HashMap<String, Literal>) -> Vec<Statement> {
+    let mut optimized = Vec::new();
+    for statement in block {
+        match statement {
+            Statement::Expr(expr) => {
+                optimized.push(Statement::Expr(fold_expression(expr, consts)));
+            }
+            Statement::Decl(mut decl) => {
+                decl.val = fold_expr(decl.val, consts);
+                optimized.push(Statement::Decl(decl));
+            }
+            Statement::Assign(mut assign) => {
+                assign.val = fold_expr(assign.val, consts);
+                optimized.push(Statement::Assign(assign));
+            }
+            Statement::If(mut ifblock) => {
+                ifblock.cond = fold_expression(ifblock.cond, consts);
+                // the type checker doesn't let inner blocks reuse names that are in
+                // scope, so the side that runs can go right into this block
+                match ifblock.cond.expr {
+                    ExprAST::Lit(Literal::Bool(true)) => {
+                        optimized.append(&mut optimize_block(ifblock.tcode, consts));
+                    }
+                    ExprAST::Lit(Literal::Bool(false)) => {
+                        optimized.append(&mut optimize_block(ifblock.ecode, consts));
+                    }
+                    _ => {
+                        ifblock.tcode = optimize_block(ifblock.tcode, consts);
+                        ifblock.ecode = optimize_block(ifblock.ecode, consts);
+                        optimized.push(Statement::If(ifblock));
+                    }
+                }
+            }
+            Statement::While(mut whileblock) => {
+                whileblock.cond = fold_expression(whileblock.cond, consts);
+                // a loop that never starts does nothing
+                if let ExprAST::Lit(Literal::Bool(false)) = whileblock.cond.expr {
+                    continue;
+                }
+                whileblock.code = optimize_block(whileblock.code, consts);
+                optimized.push(Statement::While(whileblock));
+            }
+            Statement::Return(mut ret) => {
+                ret.expr = fold_expression(ret.expr, consts);
+                optimized.push(Statement::Return(ret));
+            }
+        }
+    }
+    optimized
+}
+
+fn fold_expression(expression: Expression, consts: &HashMap<String, Literal>) -> Expression {
+    Expression {
+        expr: fold_expr(expression.expr, consts),
+        loc: expression.loc,
+    }
+}
+
+fn fold_expr(expr: ExprAST, consts: &HashMap<String, Literal>) -> ExprAST {
+    match expr {
+        ExprAST::Var(name, loc) => match consts.get(&name) {
+            Some(lit) => ExprAST::Lit(lit.to_owned()),
+            None => ExprAST::Var(name, loc),
+        },
+        ExprAST::Lit(lit) => ExprAST::Lit(lit),
+        ExprAST::BinOp(op, lhs, rhs) => {
+            let lhs = fold_expr(*lhs, consts);
+            let rhs = fold_expr(*rhs, consts);
+            if let (ExprAST::Lit(x), ExprAST::Lit(y)) = (&lhs, &rhs)
+                && let Some(lit) = fold_binop(&op, x.to_owned(), y.to_owned())
+            {
+                return ExprAST::Lit(lit);
+            }
+            simplify_binop(op, lhs, rhs)
+        }
+        ExprAST::Call(name, args, loc) => ExprAST::Call(
+            name,
+            args.into_iter()
+                .map(|arg| fold_expression(arg, consts))
+                .collect(),
+            loc,
+        ),
+        ExprAST::Casted(typ, expr) => {
+            let expr = fold_expr(*expr, consts);
+            if let ExprAST::Lit(lit) = &expr
+                && let Some(lit) = fold_cast(&typ, lit.to_owned())
+            {
+                return ExprAST::Lit(lit);
+            }
+            ExprAST::Casted(typ, Box::new(expr))
+        }
+        ExprAST::DotOp(op, expr) => {
+            let op = match op {
+                DotOp::Push(val) => DotOp::Push(Box::new(fold_expr(*val, consts))),
+                op => op,
+            };
+            ExprAST::DotOp(op, Box::new(fold_expr(*expr, consts)))
+        }
+        ExprAST::Indexed(arr, index) => ExprAST::Indexed(
+            Box::new(fold_expr(*arr, consts)),
+            Box::new(fold_expr(*index, consts)),
+        ),
+    }
+}
+
+// Only ints, `x + 0.0` isn't always x for dcmls (it turns -0.0 into 0.0).
+// The side that is dropped is a literal, so no calls are ever lost.
+fn simplify_binop(op: Operator, lhs: ExprAST, rhs: ExprAST) -> ExprAST {
+    use Literal::Int;
+    match (&op, &lhs, &rhs) {
+        (Operator::Add | Operator::Sub, _, ExprAST::Lit(Int(0)))
+        | (Operator::Mult | Operator::Div, _, ExprAST::Lit(Int(1))) => lhs,
+        (Operator::Add, ExprAST::Lit(Int(0)), _) | (Operator::Mult, ExprAST::Lit(Int(1)), _) => rhs,
+        _ => ExprAST::BinOp(op, Box::new(lhs), Box::new(rhs)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::optimize;
+    use crate::lang::{
+        DEFAULT_SEED,
+        ast::{ExprAST, Statement},
+        host::HostRegistry,
+        keywords::ENGLISH,
+        lexer::LexEngine,
+        parser::ParsingMachine,
+        run_code_with_host,
+        test_util::run,
+        tokens::{Literal, Operator, Type},
+        wrapped_val::WrappedVal,
+    };
+
+    // main, after optimizing
+    fn optimized_main(code: &str) -> Vec<Statement> {
+        let toks = LexEngine::with_keywords(code.to_owned(), &ENGLISH)
+            .lex_all()
+            .unwrap();
+        let (ast, global_consts, errors) = ParsingMachine::new(toks).parse_all();
+        assert!(errors.is_empty(), "{:?}", errors);
+        optimize(ast, &global_consts)
+            .into_iter()
+            .find(|func| func.name == "main")
+            .unwrap()
+            .code
+    }
+
+    // the value of every declaration in main, in order
+    fn decl_vals(code: &str) -> Vec<ExprAST> {
+        optimized_main(code)
+            .into_iter()
+            .filter_map(|statement| match statement {
+                Statement::Decl(decl) => Some(decl.val),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn lit(expr: &ExprAST) -> Option<&Literal> {
+        match expr {
+            ExprAST::Lit(lit) => Some(lit),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn folds_math_comparisons_and_casts() {
+        let code = "const int SIZE = 10;
+fun main() -> int {
+    int a = 2 * 3 + 4;
+    int b = SIZE * 2 - 1;
+    bool c = 3 < 4 == true;
+    bool d = 1.5 >= 2.5;
+    dcml e = 3 become dcml;
+    int f = 2.7 become int;
+    int g = (1 << 4) ** 2 % 7;
+    return 0;
+}";
+        let vals = decl_vals(code);
+        let lits: Vec<Option<&Literal>> = vals.iter().map(lit).collect();
+        assert_eq!(
+            lits,
+            vec![
+                Some(&Literal::Int(10)),
+                Some(&Literal::Int(19)),
+                Some(&Literal::Bool(true)),
+                Some(&Literal::Bool(false)),
+                Some(&Literal::Dcml(3.0)),
+                Some(&Literal::Int(2)),
+                Some(&Literal::Int(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_what_would_crash() {
+        let code = "fun main() -> int {
+    int a = 2 ** 40;
+    int b = 1 << 32;
+    int c = 2147483647 + 1;
+    return 0;
+}";
+        for val in decl_vals(code) {
+            assert!(matches!(val, ExprAST::BinOp(..)), "{:?}", val);
+        }
+    }
+
+    #[test]
+    fn drops_times_one_and_plus_zero() {
+        let code = "fun main() -> int {
+    int x = 5;
+    int a = x * 1;
+    int b = 1 * x;
+    int c = x + 0;
+    int d = 0 + x;
+    int e = x - 0;
+    int f = x / 1;
+    int g = (x + 0) * (2 - 1);
+    return 0;
+}";
+        let vals = decl_vals(code);
+        for val in &vals[1..] {
+            assert!(
+                matches!(val, ExprAST::Var(name, _) if name == "x"),
+                "{:?}",
+                val
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_what_only_looks_like_it_does_nothing() {
+        // 0 - x and 1 / x aren't x, and x + 0.0 turns -0.0 into 0.0
+        let code = "fun main() -> int {
+    int x = 5;
+    dcml y = 0.5;
+    int a = 0 - x;
+    int b = 1 / x;
+    dcml c = y + 0.0;
+    dcml d = y * 1.0;
+    return 0;
+}";
+        let vals = decl_vals(code);
+        for val in &vals[2..] {
+            assert!(matches!(val, ExprAST::BinOp(..)), "{:?}", val);
+        }
+    }
+
+    #[test]
+    fn prunes_constant_ifs() {
+        let code = "const bool DEBUG = false;
+fun main() -> int {
+    int x = 0;
+    if true { x = 1; } else { x = 2; }
+    if DEBUG { x = 3; } else { x = 4; }
+    if 1 > 2 { x = 5; }
+    if x > 2 { x = 6; }
+    while false { x = 7; }
+    return x;
+}";
+        let statements = optimized_main(code);
+        let assigned: Vec<String> = statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Assign(assign) => Some(format!("{:?}", assign.val)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(assigned, vec!["Lit(Int(1))", "Lit(Int(4))"]);
+        assert!(matches!(
+            statements[3],
+            Statement::If(ref ifblock)
+                if matches!(ifblock.cond.expr, ExprAST::BinOp(Operator::Greater, ..))
+        ));
+        assert_eq!(statements.len(), 5);
+    }
+
+    #[test]
+    fn inlined_ifs_can_reuse_names() {
+        // every `t` goes into main's block once the ifs are gone
+        let code = "fun main() -> int {
+    int x = 0;
+    if true { int t = 1; x = x + t; }
+    if true { dcml t = 20.0; x = x + (t become int); }
+    if false { x = 1000; } else { int t = 300; x = x + t; }
+    if true { if true { bool t = true; if t { x = x + 4000; } } }
+    int t = 50000;
+    return x + t;
+}";
+        let decls = optimized_main(code)
+            .into_iter()
+            .filter(|statement| matches!(statement, Statement::Decl(decl) if decl.ident == "t"))
+            .count();
+        assert_eq!(decls, 5);
+        assert_eq!(run(code), 54321);
+    }
+
+    #[test]
+    fn calls_stay_in_order() {
+        // note(n) writes n down and gives it back
+        let notes = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&notes);
+        let mut host = HostRegistry::new();
+        host.register("note", vec![Type::Int], Type::Int, move |args| {
+            let WrappedVal::Int(n) = args[0] else {
+                unreachable!()
+            };
+            log.lock().unwrap().push(n);
+            Ok(WrappedVal::Int(n))
+        })
+        .unwrap();
+        let code = "fun add(int a, int b) -> int { return a + b; }
+fun main() -> int {
+    int a = note(1) * 1 + 0;
+    int b = (2 * 3 - 6) + note(2);
+    int c = add(note(3) * (1.5 become int), note(4) + (2 - 2));
+    if true { note(5); }
+    if false { note(100); }
+    int d = 0 + note(6) * (4 - 3);
+    return a + b + c + d;
+}";
+        let res = run_code_with_host(code.to_owned(), &ENGLISH, DEFAULT_SEED, host);
+        assert_eq!(res.unwrap().0, 16);
+        assert_eq!(*notes.lock().unwrap(), vec![1, 2, 3, 4, 5, 6]);
+    }
+}
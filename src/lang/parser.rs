@@ -2,8 +2,10 @@ use std::collections::VecDeque;
 
 use crate::lang::ast::Assignment;
 
-use super::ast::{Declaration, DotOp, Expression, IfBlock, Return, WhileBlock};
+use super::ast::{Declaration, DotOp, Expression, GlobalConst, IfBlock, Return, WhileBlock};
 
+use super::keywords::all_keywords;
+use super::suggest::suggest;
 use super::tokens::Operator;
 use super::{
     ast::{ExprAST, FunctionAst, Loc, Statement},
@@ -15,6 +17,9 @@ pub struct ParsingMachine {
     cur_tok: (Token, Loc),
     tok_vec: VecDeque<(Token, Loc)>,
     finished: bool,
+    errors: Vec<CompileError>,
+    // the token before cur_tok, where the last thing parsed ended
+    last_loc: Loc,
 }
 impl ParsingMachine {
     pub fn new(all_tha_tokens: Vec<(Token, Loc)>) -> Self {
@@ -22,11 +27,15 @@ impl ParsingMachine {
         //     return None;
         // }
         let mut tok_vec: VecDeque<(Token, Loc)> = all_tha_tokens.into_iter().collect();
-        let cur_tok = tok_vec.pop_front().expect("needed one token");
+        let cur_tok = tok_vec
+            .pop_front()
+            .unwrap_or((Token::EndOfFile, Loc::new(1, 1)));
         ParsingMachine {
+            finished: matches!(cur_tok.0, Token::EndOfFile),
+            last_loc: cur_tok.1,
             cur_tok,
             tok_vec,
-            finished: false,
+            errors: Vec::new(),
         }
     }
 
@@ -37,25 +46,87 @@ impl ParsingMachine {
         }
         self.finished = false;
     }
-    pub fn parse_all(mut self) -> Result<Vec<FunctionAst>, CompileError> {
+    /// Parses everything, carrying on after errors so they are all reported at once.
+    /// Functions and constants with an error in them are left out of the AST,
+    /// so later passes can still check the parts that parsed fine.
+    pub fn parse_all(mut self) -> (Vec<FunctionAst>, Vec<GlobalConst>, Vec<CompileError>) {
         let mut all_funcs = Vec::new();
+        let mut all_consts = Vec::new();
         while !self.finished {
-            all_funcs.push(self.parse_function()?);
+            let errors_before = self.errors.len();
+            if let Token::Const = self.cur_tok.0 {
+                match self.parse_global_const() {
+                    Ok(global) => all_consts.push(global),
+                    Err(e) => {
+                        self.errors.push(e);
+                        self.sync_item();
+                    }
+                }
+            } else {
+                match self.parse_function() {
+                    // errors inside the body were already recovered from
+                    Ok(func) if self.errors.len() == errors_before => all_funcs.push(func),
+                    Ok(_) => (),
+                    Err(e) => {
+                        self.errors.push(e);
+                        self.sync_item();
+                    }
+                }
+            }
+        }
+        (all_funcs, all_consts, self.errors)
+    }
+    fn parse_global_const(&mut self) -> Result<GlobalConst, CompileError> {
+        self.eat_tok(); // eat const
+        let Token::DeclareType(typ) = self.cur_tok.0.clone() else {
+            return Err(self.err(ErrorType::ExpectedGlobalConstType));
+        };
+        self.eat_tok(); // eat type
+        let (Token::Ident(ident), ident_loc) = self.cur_tok.clone() else {
+            return Err(self.err(ErrorType::ExpectedName {
+                after: typ.to_string(),
+            }));
+        };
+        self.eat_tok(); // eat ident
+        if !matches!(self.cur_tok.0, Token::Assign) {
+            return Err(self.err(ErrorType::ExpectedAssign { name: ident }));
+        }
+        self.eat_tok();
+        let expr = self.parse_expression()?;
+        let Token::Semicolon = self.cur_tok.0 else {
+            return Err(self.err_after_last(ErrorType::ExpectedSemicolon));
+        };
+        self.eat_tok();
+        if let Token::EndOfFile = self.cur_tok.0 {
+            self.finished = true;
         }
-        Ok(all_funcs)
+        Ok(GlobalConst {
+            typ,
+            ident,
+            ident_loc,
+            val: expr.expr,
+            val_loc: expr.loc,
+        })
     }
     pub fn parse_function(&mut self) -> Result<FunctionAst, CompileError> {
         let (Token::Fun, loc) = &self.cur_tok else {
-            return Err(self.err("Did not find keyword 'fun'.".to_string()));
+            if let Token::Ident(word) = self.cur_tok.0.to_owned() {
+                return Err(self.err_unknown_word(&word, ["fun", "const"]));
+            }
+            return Err(self.err(ErrorType::ExpectedFun));
         };
-        let loc = loc.to_owned();
+        let fun_loc = loc.to_owned();
         self.eat_tok();
         let Token::Ident(func_ident) = self.cur_tok.0.clone() else {
-            return Err(self.err("Expected function name after 'fun' keyword".to_string()));
+            return Err(self.err(ErrorType::ExpectedName {
+                after: "fun".to_string(),
+            }));
         };
+        // `fun name`, so errors about the function point at both
+        let loc = Loc::span(fun_loc, self.cur_tok.1);
         self.eat_tok();
         let Token::LeftParen = self.cur_tok.0.clone() else {
-            return Err(self.err("Expected left parenthesis after function name".to_string()));
+            return Err(self.err(ErrorType::ExpectedLeftParen { name: func_ident }));
         };
         self.eat_tok();
         // there will be some WEIRD bugs with commas
@@ -64,13 +135,13 @@ impl ParsingMachine {
         let mut param_vec: Vec<(String, Type)> = Vec::new();
         while !matches!(self.cur_tok.0, Token::RightParen) {
             let Token::DeclareType(typ) = self.cur_tok.0.clone() else {
-                return Err(self.err("Expected a type in function parameters".to_string()));
+                return Err(self.err(ErrorType::ExpectedParamType));
             };
             self.eat_tok(); // eats the type
             let Token::Ident(arg_name) = self.cur_tok.0.clone() else {
-                return Err(
-                    self.err("Expected ident after type in function parameters".to_string())
-                );
+                return Err(self.err(ErrorType::ExpectedName {
+                    after: typ.to_string(),
+                }));
             };
             self.eat_tok();
             // eats the ident
@@ -82,19 +153,17 @@ impl ParsingMachine {
                 }
                 Token::RightParen => break,
                 _ => {
-                    return Err(
-                        self.err("Expected ',' or ')' after parameter in function".to_string())
-                    );
+                    return Err(self.err(ErrorType::ExpectedParamSeparator));
                 }
             }
         }
         self.eat_tok();
         let Token::RArrow = self.cur_tok.0 else {
-            return Err(self.err("Expected return arrow ('->')".to_string()));
+            return Err(self.err(ErrorType::ExpectedArrow));
         };
         self.eat_tok();
         let Token::DeclareType(ret_type) = &self.cur_tok.0 else {
-            return Err(self.err("Expected return arrow to point to type".to_string()));
+            return Err(self.err(ErrorType::ExpectedReturnType));
         };
         let ret_type = ret_type.to_owned();
         self.eat_tok();
@@ -112,50 +181,97 @@ impl ParsingMachine {
     }
     fn collect_curly_statements(&mut self) -> Result<Vec<Statement>, CompileError> {
         if !matches!(self.cur_tok.0, Token::LeftCurly) {
-            return Err(self.err("Expected a block".to_string()));
+            return Err(self.err(ErrorType::ExpectedBlock {
+                keyword: "fun".to_string(),
+            }));
         }
         // let stloc = self.cur_tok.1.clone();
         self.eat_tok(); // eat left curly
         let mut state_vec: Vec<Statement> = Vec::new();
         while !matches!(self.cur_tok.0, Token::RightCurly) {
-            let statement = self.parse_statement()?;
-            state_vec.push(statement);
+            if let Token::EndOfFile | Token::Fun = self.cur_tok.0 {
+                return Err(self.err(ErrorType::UnclosedBlock));
+            }
+            match self.parse_statement() {
+                Ok(statement) => state_vec.push(statement),
+                // an inner block that never closed, nothing left to recover in this one
+                Err(e) if matches!(self.cur_tok.0, Token::EndOfFile | Token::Fun) => {
+                    return Err(e);
+                }
+                Err(e) => {
+                    self.errors.push(e);
+                    self.sync_statement();
+                }
+            }
         }
         self.eat_tok(); // eats right curly
         Ok(state_vec)
     }
     fn parse_statement(&mut self) -> Result<Statement, CompileError> {
         match self.cur_tok.0 {
-            Token::DeclareType(_) => self.parse_decl(),
+            Token::DeclareType(_) | Token::Var => self.parse_decl(),
+            Token::Const => {
+                let const_loc = self.cur_tok.1;
+                self.eat_tok(); // eat const
+                let (Token::DeclareType(_) | Token::Var) = self.cur_tok.0 else {
+                    return Err(self.err(ErrorType::ExpectedLocalConstType));
+                };
+                let Statement::Decl(mut decl) = self.parse_decl()? else {
+                    unreachable!()
+                };
+                decl.constant = true;
+                decl.loc = Loc::span(const_loc, decl.loc);
+                Ok(Statement::Decl(decl))
+            }
             Token::Ident(_) => match self.peek_tok() {
-                Some(&(Token::LeftParen, _)) => Ok(Statement::Expr(self.parse_expression()?)),
+                Some(&(Token::LeftParen, _)) => self.parse_expr_statement(),
                 Some(&(Token::Assign, _)) => self.parse_assign(),
-                _ => Ok(Statement::Expr(self.parse_expression()?)),
+                // two things in a row like `whle x` or `retrun 5` means the word
+                // was probably a misspelled keyword
+                Some(&(Token::Ident(_) | Token::Lit(_), _)) => {
+                    let Token::Ident(word) = self.cur_tok.0.to_owned() else {
+                        unreachable!()
+                    };
+                    Err(self.err_unknown_word(&word, all_keywords()))
+                }
+                _ => self.parse_expr_statement(),
             },
             Token::If => self.parse_if(),
             Token::While => self.parse_while(),
             Token::Return => {
+                let start = self.cur_tok.1;
                 self.eat_tok();
+                let expr = self.parse_expression()?;
                 let ans = Ok(Statement::Return(Return {
-                    expr: self.parse_expression()?,
-                    loc: self.cur_tok.1.clone(),
+                    expr,
+                    loc: Loc::span(start, self.last_loc),
                 }));
                 let Token::Semicolon = self.cur_tok.0 else {
-                    return Err(self.err("Expected semicolon after return statement".to_owned()));
+                    return Err(self.err_after_last(ErrorType::ExpectedSemicolon));
                 };
                 self.eat_tok();
                 ans
             }
-            _ => Err(self.err("Unexpected Token".to_owned())),
+            _ => Err(self.err(ErrorType::UnexpectedToken)),
         }
     }
+    fn parse_expr_statement(&mut self) -> Result<Statement, CompileError> {
+        let expr = self.parse_expression()?;
+        let Token::Semicolon = self.cur_tok.0 else {
+            return Err(self.err_after_last(ErrorType::ExpectedSemicolon));
+        };
+        self.eat_tok();
+        Ok(Statement::Expr(expr))
+    }
     fn parse_if(&mut self) -> Result<Statement, CompileError> {
         let loc = self.cur_tok.1.clone();
         self.eat_tok();
         // ate the if
         let cond = self.parse_expression()?;
         let Token::LeftCurly = self.cur_tok.0 else {
-            return Err(self.err("Expected block after if conditional".to_string()));
+            return Err(self.err(ErrorType::ExpectedBlock {
+                keyword: "if".to_string(),
+            }));
         };
         let ecode;
         let block = self.collect_curly_statements()?;
@@ -182,7 +298,9 @@ impl ParsingMachine {
         // ate the while
         let cond = self.parse_expression()?;
         let Token::LeftCurly = self.cur_tok.0 else {
-            return Err(self.err("Expected block after while conditional".to_string()));
+            return Err(self.err(ErrorType::ExpectedBlock {
+                keyword: "while".to_string(),
+            }));
         };
         let block = self.collect_curly_statements()?;
         Ok(Statement::While(WhileBlock {
@@ -193,32 +311,38 @@ impl ParsingMachine {
     }
 
     fn parse_decl(&mut self) -> Result<Statement, CompileError> {
-        let Token::DeclareType(typ) = self.cur_tok.0.clone() else {
-            unreachable!();
+        let typ = match self.cur_tok.0.clone() {
+            Token::DeclareType(typ) => Some(typ),
+            Token::Var => None,
+            _ => unreachable!(),
         };
+        let start = self.cur_tok.1;
         self.eat_tok(); // eat type
         let (Token::Ident(ident), loc) = self.cur_tok.clone() else {
-            return Err(self.err("Expected ident after type declaration".to_string()));
+            let after = match &typ {
+                Some(typ) => typ.to_string(),
+                None => "var".to_string(),
+            };
+            return Err(self.err(ErrorType::ExpectedName { after }));
         };
         self.eat_tok(); // eat ident
         if !matches!(self.cur_tok.0, Token::Assign) {
-            return Err(self.err(
-                "Expected an '=' after declaration (you have to declare and assign in one line)"
-                    .to_string(),
-            ));
+            return Err(self.err(ErrorType::ExpectedAssign { name: ident }));
         }
         self.eat_tok();
         let expr = self.parse_expression()?;
         let Token::Semicolon = self.cur_tok.0 else {
-            return Err(self.err("Expected a semicolon".to_string()));
+            return Err(self.err_after_last(ErrorType::ExpectedSemicolon));
         };
         self.eat_tok();
         Ok(Statement::Decl(Declaration {
+            constant: false,
             typ,
             ident,
             ident_loc: loc,
             val: expr.expr,
             val_loc: expr.loc,
+            loc: Loc::span(start, expr.loc),
         }))
     }
     fn parse_assign(&mut self) -> Result<Statement, CompileError> {
@@ -230,10 +354,11 @@ impl ParsingMachine {
             unreachable!()
         };
         self.eat_tok();
-        let v_loc = self.cur_tok.1.clone();
+        let start = self.cur_tok.1;
         let expr = self.parse_expr()?;
+        let v_loc = Loc::span(start, self.last_loc);
         let Token::Semicolon = self.cur_tok.0 else {
-            return Err(self.err("Expected a semicolon".to_string()));
+            return Err(self.err_after_last(ErrorType::ExpectedSemicolon));
         };
         self.eat_tok();
         Ok(Statement::Assign(Assignment {
@@ -244,12 +369,12 @@ impl ParsingMachine {
         }))
     }
     fn parse_expression(&mut self) -> Result<Expression, CompileError> {
-        let ans = Ok(Expression {
-            expr: self.parse_expr()?,
-            loc: self.cur_tok.1.clone(),
-        });
-        // self.eat_tok();
-        ans
+        let start = self.cur_tok.1;
+        let expr = self.parse_expr()?;
+        Ok(Expression {
+            expr,
+            loc: Loc::span(start, self.last_loc),
+        })
     }
     fn parse_expr(&mut self) -> Result<ExprAST, CompileError> {
         let lhs = self.parse_primary()?;
@@ -271,16 +396,19 @@ impl ParsingMachine {
             if let Token::Op(new_binop) = self.cur_tok.0.clone() {
                 if ParsingMachine::get_priority(&new_binop) > tok_prior {
                     rhs = self.parse_rhs(tok_prior + 1, rhs)?;
+                } else if binop == Operator::Pow && new_binop == Operator::Pow {
+                    // power is right associative, 2 ** 3 ** 2 is 2 ** (3 ** 2)
+                    rhs = self.parse_rhs(tok_prior, rhs)?;
                 }
             }
             lhs = ExprAST::BinOp(binop, Box::new(lhs), Box::new(rhs));
         }
     }
     fn parse_ident(&mut self) -> Result<ExprAST, CompileError> {
-        let Token::Ident(ident) = &self.cur_tok.0 else {
+        let (Token::Ident(ident), ident_loc) = &self.cur_tok else {
             unreachable!()
         };
-        let ident = ident.clone();
+        let (ident, ident_loc) = (ident.clone(), ident_loc.to_owned());
 
         self.eat_tok();
         if let Token::LeftParen = self.cur_tok.0 {
@@ -302,24 +430,23 @@ impl ParsingMachine {
                         break;
                     }
                     _ => {
-                        return Err(
-                            self.err("Expected a ',' or ')' in call parameters".to_string())
-                        );
+                        return Err(self.err(ErrorType::ExpectedCallSeparator));
                     }
                 }
             }
             self.eat_tok();
-            return Ok(ExprAST::Call(ident, param_vec));
+            return Ok(ExprAST::Call(ident, param_vec, ident_loc));
         } else {
-            return Ok(ExprAST::Var(ident));
+            return Ok(ExprAST::Var(ident, ident_loc));
         }
     }
     fn parse_paren(&mut self) -> Result<ExprAST, CompileError> {
         self.eat_tok(); // the left parenthesis
         let expr = self.parse_expr()?;
         let Token::RightParen = self.cur_tok.0 else {
-            return Err(self.err("Expected a closed parenthesis in expression".to_string()));
+            return Err(self.err(ErrorType::ExpectedCloseParen));
         };
+        self.eat_tok(); // the right parenthesis
         return Ok(expr);
     }
     fn parse_primary(&mut self) -> Result<ExprAST, CompileError> {
@@ -327,10 +454,12 @@ impl ParsingMachine {
             Token::Ident(_) => {
                 let ident_expr = self.parse_ident()?;
                 if let Token::LeftBrack = self.cur_tok.0 {
+                    self.eat_tok(); // the left bracket
                     let index_expr = self.parse_expression()?;
                     let Token::RightBrack = self.cur_tok.0 else {
-                        todo!()
+                        return Err(self.err(ErrorType::ExpectedCloseBracket));
                     };
+                    self.eat_tok(); // the right bracket
                     ExprAST::Indexed(Box::new(ident_expr), Box::new(index_expr.expr))
                 } else {
                     ident_expr
@@ -343,29 +472,29 @@ impl ParsingMachine {
             }
             Token::LeftParen => self.parse_paren()?,
             _ => {
-                return Err(self.err(
-                    "Expected an Identifier, Literal, or '(', got unknown token".to_string(),
-                ));
+                return Err(self.err(ErrorType::ExpectedValue));
             }
         };
         match self.cur_tok.0 {
             Token::Dot(ref d_str) => {
                 let d_str = d_str.to_owned();
+                let dot_loc = self.cur_tok.1;
                 self.eat_tok();
                 let dot_op = match d_str.as_str() {
                     "len" => DotOp::Len,
                     "pop" => DotOp::Pop,
                     "push" => {
                         let Token::LeftParen = self.cur_tok.0 else {
-                            return Err(self.err(format!("Expected '(' after push statement")));
+                            return Err(self.err(ErrorType::ExpectedParenAfterPush));
                         };
                         let expr = self.parse_paren()?;
                         DotOp::Push(Box::new(expr))
                     }
                     _ => {
-                        return Err(
-                            self.err(format!("Found unexpected token after dot_op: '{}'", d_str))
-                        );
+                        return Err(CompileError::at(
+                            ErrorType::UnknownDotOp { name: d_str },
+                            &dot_loc,
+                        ));
                     }
                 };
                 return Ok(ExprAST::DotOp(dot_op, Box::new(ans)));
@@ -376,10 +505,7 @@ impl ParsingMachine {
                     self.eat_tok();
                     return Ok(ExprAST::Casted(data_type, Box::new(ans)));
                 } else {
-                    return Err(self.err(format!(
-                        "Expected datatype after cast, found {:#?}",
-                        self.cur_tok.0
-                    )));
+                    return Err(self.err(ErrorType::ExpectedCastType));
                 }
             }
             _ => return Ok(ans),
@@ -387,11 +513,59 @@ impl ParsingMachine {
     }
 
     fn eat_tok(&mut self) {
+        self.last_loc = self.cur_tok.1;
         self.cur_tok = match self.tok_vec.pop_front() {
             Some(x) => x,
-            None => (Token::EndOfFile, Loc { line: 0, col: 0 }),
+            // errors at the end of the file point at the last token
+            None => (Token::EndOfFile, self.cur_tok.1),
         };
     }
+    /// Skips the rest of a statement that had an error, up to and including its ';'
+    /// (or its block, if it had one). Stops before the '}' closing the block it was in.
+    fn sync_statement(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.cur_tok.0 {
+                Token::EndOfFile | Token::Fun => return,
+                Token::Semicolon if depth == 0 => {
+                    self.eat_tok();
+                    return;
+                }
+                Token::LeftCurly => depth += 1,
+                Token::RightCurly if depth == 0 => return,
+                Token::RightCurly => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.eat_tok();
+                        // the else belongs to the broken if too
+                        if !matches!(self.cur_tok.0, Token::Else) {
+                            return;
+                        }
+                    }
+                }
+                _ => (),
+            }
+            self.eat_tok();
+        }
+    }
+    /// Skips to the next function or global constant after one that had an error.
+    fn sync_item(&mut self) {
+        let mut depth: u32 = 0;
+        loop {
+            match self.cur_tok.0 {
+                Token::EndOfFile => {
+                    self.finished = true;
+                    return;
+                }
+                Token::Fun => return,
+                Token::Const if depth == 0 => return,
+                Token::LeftCurly => depth += 1,
+                Token::RightCurly => depth = depth.saturating_sub(1),
+                _ => (),
+            }
+            self.eat_tok();
+        }
+    }
     fn peek_tok(&mut self) -> Option<&(Token, Loc)> {
         if self.tok_vec.len() > 0 {
             Some(&self.tok_vec[0])
@@ -409,15 +583,69 @@ impl ParsingMachine {
             | Operator::Eq
             | Operator::NEq => 20,
             Operator::BAnd | Operator::BOr | Operator::BXor => 30,
+            Operator::Shl | Operator::Shr => 35,
             Operator::Add | Operator::Sub => 40,
             Operator::Mult | Operator::Div | Operator::Mod => 50,
+            Operator::Pow => 60,
         }
     }
-    fn err(&self, err_name: String) -> CompileError {
-        return CompileError {
-            e_type: ErrorType::ParsingError(err_name),
-            line: self.cur_tok.1.line,
-            col: self.cur_tok.1.col,
+    fn err_unknown_word<'a>(
+        &self,
+        word: &str,
+        keywords: impl IntoIterator<Item = &'a str>,
+    ) -> CompileError {
+        let suggestions = suggest(word, keywords);
+        self.err(ErrorType::UnknownWord {
+            word: word.to_owned(),
+        })
+        .with_suggestions(suggestions)
+    }
+    // for things missing at the end of a line, point right after the last token
+    // instead of at the start of the next line
+    fn err_after_last(&self, e_type: ErrorType) -> CompileError {
+        let after = Loc::new(self.last_loc.end_line, self.last_loc.end_col);
+        CompileError::at(e_type, &after)
+    }
+    fn err(&self, e_type: ErrorType) -> CompileError {
+        return CompileError::at(e_type, &self.cur_tok.1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::{
+        DEFAULT_SEED, errors::ErrorType, keywords::ENGLISH, run_code, test_util::run,
+    };
+
+    #[test]
+    fn shift_binds_looser_than_add() {
+        assert_eq!(run("fun main() -> int { return 1 + 2 << 3; }"), 24);
+        assert_eq!(run("fun main() -> int { return 64 >> 1 + 2; }"), 8);
+    }
+
+    #[test]
+    fn power_binds_tighter_than_mult() {
+        assert_eq!(run("fun main() -> int { return 3 * 2 ** 2; }"), 12);
+        assert_eq!(run("fun main() -> int { return 2 ** 2 * 3; }"), 12);
+    }
+
+    #[test]
+    fn power_is_right_associative() {
+        // (2 ** 3) ** 2 would be 64
+        assert_eq!(run("fun main() -> int { return 2 ** 3 ** 2; }"), 512);
+    }
+
+    #[test]
+    fn declarations_start_at_their_type() {
+        let unreachable = |dead: &str| {
+            let code = format!("fun main() -> int {{\n    return 1;\n    {}\n}}", dead);
+            let (_, warnings) = run_code(code, &ENGLISH, DEFAULT_SEED).unwrap();
+            let warn = &warnings[0];
+            assert_eq!(warn.e_type, ErrorType::UnreachableCode);
+            (warn.line, warn.col, warn.end_line, warn.end_col)
         };
+        assert_eq!(unreachable("int dead = 3;"), (3, 5, 3, 17));
+        assert_eq!(unreachable("var dead = 3;"), (3, 5, 3, 17));
+        assert_eq!(unreachable("const int dead = 3;"), (3, 5, 3, 23));
     }
 }
@@ -0,0 +1,181 @@
+use std::collections::{HashMap, HashSet};
+
+use super::asm::{Cmp, Instruction};
+
+/// Cleans up the instructions the compiler makes, a few at a time. Jumps that land on
+/// another jump go straight to where that one goes, and jumps to the very next
+/// instruction are dropped. Then instructions that always come together are put into
+/// one: `eq not` into `ne`, `mov pop` into `movpop`, and a comparison then `jnz` into
+/// `jmpiffalse`. A `push` right before a `pop` does nothing, so both go.
+/// Labels are always kept, and nothing is joined across one, since something could jump there.
+pub fn peephole(code: Vec<Instruction>) -> Vec<Instruction> {
+    let code = thread_jumps(code);
+    let code = remove_useless_jumps(code);
+    fuse(code)
+}
+
+fn thread_jumps(mut code: Vec<Instruction>) -> Vec<Instruction> {
+    let labels = code
+        .iter()
+        .enumerate()
+        .filter_map(|(ind, inst)| match inst {
+            Instruction::Label(name) => Some((name.to_owned(), ind)),
+            _ => None,
+        })
+        .collect::<HashMap<String, usize>>();
+    let mut threaded = HashMap::new();
+    for name in labels.keys() {
+        threaded.insert(name.to_owned(), final_target(&code, &labels, name));
+    }
+    for inst in code.iter_mut() {
+        match inst {
+            Instruction::Jmp(target) | Instruction::Jz(target) | Instruction::Jnz(target) => {
+                *target = threaded[target].to_owned();
+            }
+            _ => (),
+        }
+    }
+    code
+}
+
+// follows jumps from the label until one lands somewhere that isn't a jump.
+// `while true {}` jumps to itself forever, so labels that were already seen stop it
+fn final_target(code: &[Instruction], labels: &HashMap<String, usize>, name: &str) -> String {
+    let mut name = name.to_owned();
+    let mut seen = HashSet::new();
+    while seen.insert(name.to_owned()) {
+        let next = code[labels[&name]..]
+            .iter()
+            .find(|inst| !matches!(inst, Instruction::Label(_)));
+        match next {
+            Some(Instruction::Jmp(target)) => name = target.to_owned(),
+            _ => break,
+        }
+    }
+    name
+}
+
+// `jmp end` right before `label end`, like the end of an if without an else
+fn remove_useless_jumps(code: Vec<Instruction>) -> Vec<Instruction> {
+    let useless = code
+        .iter()
+        .enumerate()
+        .filter(|(ind, inst)| match inst {
+            Instruction::Jmp(target) => code[ind + 1..]
+                .iter()
+                .take_while(|inst| matches!(inst, Instruction::Label(_)))
+                .any(|inst| matches!(inst, Instruction::Label(name) if name == target)),
+            _ => false,
+        })
+        .map(|(ind, _)| ind)
+        .collect::<HashSet<usize>>();
+    code.into_iter()
+        .enumerate()
+        .filter(|(ind, _)| !useless.contains(ind))
+        .map(|(_, inst)| inst)
+        .collect()
+}
+
+fn fuse(code: Vec<Instruction>) -> Vec<Instruction> {
+    let mut fused: Vec<Instruction> = Vec::new();
+    for inst in code {
+        let last = fused.pop();
+        match (last, inst) {
+            (Some(Instruction::Eq), Instruction::Not) => fused.push(Instruction::Ne),
+            (Some(Instruction::Mov(offset)), Instruction::Pop) => {
+                fused.push(Instruction::MovPop(offset));
+            }
+            (Some(Instruction::Push(_, _)), Instruction::Pop) => (),
+            (Some(last), Instruction::Jnz(target)) => match comparison(&last) {
+                Some(cmp) => fused.push(Instruction::JmpIfFalse(cmp, target)),
+                None => {
+                    fused.push(last);
+                    fused.push(Instruction::Jnz(target));
+                }
+            },
+            (last, inst) => {
+                fused.extend(last);
+                fused.push(inst);
+            }
+        }
+    }
+    fused
+}
+
+fn comparison(inst: &Instruction) -> Option<Cmp> {
+    Some(match inst {
+        Instruction::Eq => Cmp::Eq,
+        Instruction::Ne => Cmp::Ne,
+        Instruction::L => Cmp::L,
+        Instruction::Le => Cmp::Le,
+        Instruction::G => Cmp::G,
+        Instruction::Ge => Cmp::Ge,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::peephole;
+    use crate::lang::{
+        asm::{Cmp, Instruction},
+        count_instructions,
+        keywords::ENGLISH,
+    };
+
+    #[test]
+    fn comparison_and_jnz_fuse() {
+        let code = peephole(vec![
+            Instruction::Push(0, 0),
+            Instruction::Push(0, 1),
+            Instruction::L,
+            Instruction::Jnz(String::from("end")),
+            Instruction::Push(0, 2),
+            Instruction::Pop,
+            Instruction::Label(String::from("end")),
+            Instruction::Ret,
+        ]);
+        assert!(
+            matches!(
+                code.as_slice(),
+                [
+                    Instruction::Push(0, 0),
+                    Instruction::Push(0, 1),
+                    Instruction::JmpIfFalse(Cmp::L, target),
+                    Instruction::Label(_),
+                    Instruction::Ret,
+                ] if target == "end"
+            ),
+            "{:?}",
+            code
+        );
+    }
+
+    #[test]
+    fn nothing_joins_across_a_label() {
+        let code = peephole(vec![
+            Instruction::Eq,
+            Instruction::Label(String::from("top")),
+            Instruction::Not,
+            Instruction::Jz(String::from("top")),
+        ]);
+        assert_eq!(code.len(), 4, "{:?}", code);
+    }
+
+    #[test]
+    fn same_answer_in_fewer_instructions() {
+        let code = "fun main() -> int {
+    int total = 0;
+    int i = 0;
+    while i < 100 {
+        if i % 3 == 0 { total = total + i; }
+        i = i + 1;
+    }
+    return total;
+}";
+        let (naive, naive_count) = count_instructions(code.to_owned(), &ENGLISH, false).unwrap();
+        let (fused, fused_count) = count_instructions(code.to_owned(), &ENGLISH, true).unwrap();
+        assert_eq!((naive, fused), (1683, 1683));
+        assert!(fused_count < naive_count);
+    }
+}
@@ -0,0 +1,49 @@
+/// Finds the names that are close to a misspelled one, closest first.
+/// `whle` finds `while`, `retrun` finds `return`, and `fibb` finds `fib`.
+pub fn suggest<'a>(typo: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    // short names need to be really close, or everything matches everything
+    let max_dist = match typo.chars().count() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    };
+    let mut found: Vec<(usize, String)> = Vec::new();
+    for candidate in candidates {
+        if candidate == typo || found.iter().any(|(_, name)| name == candidate) {
+            continue;
+        }
+        let dist = edit_distance(typo, candidate);
+        if dist <= max_dist {
+            found.push((dist, candidate.to_owned()));
+        }
+    }
+    found.sort();
+    found.into_iter().take(3).map(|(_, name)| name).collect()
+}
+
+/// Levenshtein distance where swapping two letters next to each other
+/// only counts as one edit, since that is the most common typo.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<char>>();
+    let b = b.chars().collect::<Vec<char>>();
+    // dist[i][j] is the distance between the first i chars of a and the first j of b
+    let mut dist = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in dist[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            dist[i][j] = (dist[i - 1][j] + 1)
+                .min(dist[i][j - 1] + 1)
+                .min(dist[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                dist[i][j] = dist[i][j].min(dist[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    dist[a.len()][b.len()]
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Token {
     Ident(String),
@@ -6,7 +8,9 @@ pub enum Token {
     Op(Operator),
     Semicolon,
     Fun,
+    Const,
     DeclareType(Type),
+    Var,
     While,
     If,
     Else,
@@ -49,6 +53,10 @@ pub enum Operator {
     BAnd,
     BOr,
     BXor,
+    Shl,
+    Shr,
+    // Exponent
+    Pow,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -60,6 +68,8 @@ pub enum Type {
     CallStack, // 5
     Array(Box<Type>),
     Void,
+    // only used by the typechecker, for things that already had an error
+    Unknown,
 }
 impl Type {
     //size in bytes including tag at end
@@ -74,6 +84,9 @@ impl Type {
             Type::Void => {
                 panic!("Tried to see size of void");
             }
+            Type::Unknown => {
+                panic!("Tried to see size of unknown");
+            }
         }) + 1
     }
     pub fn to_num(&self) -> u8 {
@@ -93,6 +106,52 @@ impl Type {
             Type::Void => {
                 panic!("Tried to get the num convert of void");
             }
+            Type::Unknown => {
+                panic!("Tried to get the num convert of unknown");
+            }
+        }
+    }
+}
+
+// how it is written in the code, for error messages
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sym = match self {
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mult => "*",
+            Operator::Div => "/",
+            Operator::Mod => "%",
+            Operator::Eq => "==",
+            Operator::NEq => "!=",
+            Operator::Less => "<",
+            Operator::LEq => "<=",
+            Operator::Greater => ">",
+            Operator::GEq => ">=",
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::Xor => "^^",
+            Operator::BAnd => "&",
+            Operator::BOr => "|",
+            Operator::BXor => "^",
+            Operator::Shl => "<<",
+            Operator::Shr => ">>",
+            Operator::Pow => "**",
+        };
+        write!(f, "{}", sym)
+    }
+}
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Dcml => write!(f, "dcml"),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "string"),
+            Type::CallStack => write!(f, "callstack"),
+            Type::Array(inner) => write!(f, "{}[]", inner),
+            Type::Void => write!(f, "void"),
+            Type::Unknown => write!(f, "unknown"),
         }
     }
 }
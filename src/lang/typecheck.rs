@@ -3,27 +3,85 @@ use std::{collections::HashMap, mem::discriminant};
 use crate::lang::tokens::Operator;
 
 use super::{
-    ast::{ExprAST, FunctionAst, Loc, Statement},
+    ast::{DotOp, ExprAST, FunctionAst, GlobalConst, Loc, Statement},
+    builtins::{BUILTINS, find_builtin, is_builtin},
+    consteval::eval_const_expr,
     errors::{CompileError, ErrorType},
+    explain::{fill, message_template},
+    host::HostRegistry,
+    keywords::all_keywords,
+    suggest::suggest,
     tokens::{Literal, Type},
 };
 
+// the loc is where the variable was declared, if it was declared const
+type VarMap = HashMap<String, (Type, Option<Loc>)>;
+
 pub struct TypeChecker {
     ast: Vec<FunctionAst>,
+    // every declaration in the function being checked, to explain
+    // variables that are used before (or outside of) where they are declared
+    func_decls: HashMap<String, Loc>,
+    consts: Vec<GlobalConst>,
     funcmap: HashMap<String, (Vec<Type>, Type)>,
+    // global constants that have been worked out already
+    constmap: HashMap<String, Literal>,
+    // the type of every global constant checked so far, `Type::Unknown` if its
+    // value had an error, so using it later doesn't give another error
+    consttypes: HashMap<String, Type>,
+    host: HostRegistry,
     errors: Vec<CompileError>,
 }
 //TODO implement void type for typechecker so we can have functions that don't return.
 impl TypeChecker {
-    pub fn new(ast: Vec<FunctionAst>) -> Self {
+    pub fn new(ast: Vec<FunctionAst>, consts: Vec<GlobalConst>) -> Self {
+        TypeChecker::new_with_host(ast, consts, HostRegistry::new())
+    }
+    pub fn new_with_host(
+        ast: Vec<FunctionAst>,
+        consts: Vec<GlobalConst>,
+        host: HostRegistry,
+    ) -> Self {
         TypeChecker {
             ast,
+            func_decls: HashMap::new(),
+            consts,
             funcmap: HashMap::new(),
+            constmap: HashMap::new(),
+            consttypes: HashMap::new(),
+            host,
             errors: Vec::new(),
         }
     }
     pub fn check_all(mut self) -> Result<(), Vec<CompileError>> {
         for func in &self.ast {
+            if is_builtin(&func.name) {
+                let err = self.err(
+                    &func.loc,
+                    ErrorType::BuiltinName {
+                        name: func.name.to_owned(),
+                    },
+                );
+                self.errors.push(err);
+            }
+            if self.host.find(&func.name).is_some() {
+                let err = self.err(
+                    &func.loc,
+                    ErrorType::HostName {
+                        name: func.name.to_owned(),
+                    },
+                );
+                self.errors.push(err);
+            }
+            if self.funcmap.contains_key(&func.name) {
+                let err = self.err(
+                    &func.loc,
+                    ErrorType::DuplicateFunction {
+                        name: func.name.to_owned(),
+                    },
+                );
+                self.errors.push(err);
+            }
             let mut arg_types = Vec::new();
             for arg in &func.params {
                 arg_types.push(arg.1.to_owned());
@@ -31,8 +89,13 @@ impl TypeChecker {
             self.funcmap
                 .insert(func.name.to_owned(), (arg_types, func.ret_type.to_owned()));
         }
+        // after the functions are known, so a constant that calls one says it
+        // can't do that, instead of that the function doesn't exist
+        for global in self.consts.to_owned() {
+            self.check_global_const(global);
+        }
         for func in self.ast.to_owned() {
-            _ = self.check_func(func.to_owned());
+            self.check_func(func);
         }
         if self.errors.len() == 0 {
             Ok(())
@@ -40,21 +103,37 @@ impl TypeChecker {
             Err(self.errors)
         }
     }
-    fn check_expr(
-        &mut self,
-        ex: ExprAST,
-        loc: Loc,
-        varmap: &HashMap<String, Type>,
-    ) -> Result<Type, CompileError> {
+    /// Finds the type of an expression. Errors are added to `self.errors` right where they
+    /// are found, and the expression gets `Type::Unknown` so whatever uses it stays quiet.
+    fn check_expr(&mut self, ex: ExprAST, loc: Loc, varmap: &VarMap) -> Type {
         match ex {
-            ExprAST::Var(ref s) => Ok(varmap.get(s).unwrap().clone()),
-            ExprAST::Lit(ref lit) => Ok(lit.get_type()),
-            ExprAST::BinOp(ref op, ex0, ex1) => Ok(
+            ExprAST::Var(ref s, ref var_loc) => match varmap.get(s) {
+                Some((typ, _)) => typ.clone(),
+                None => match self.consttypes.get(s) {
+                    Some(typ) => typ.clone(),
+                    None => {
+                        let err = self.err_missing_var(var_loc, s, varmap);
+                        self.add_err(err);
+                        Type::Unknown
+                    }
+                },
+            },
+            ExprAST::Lit(ref lit) => lit.get_type(),
+            ExprAST::BinOp(ref op, ex0, ex1) => {
+                // the right side is always 0, so this would crash every time it runs
+                if let Operator::Div | Operator::Mod = op
+                    && let Some(Literal::Int(0)) = eval_const_expr(&ex1, &self.constmap)
+                {
+                    let err = self.err(&loc, ErrorType::DivByZero);
+                    self.add_err(err);
+                }
                 match (
                     op,
-                    self.check_expr(*ex0.to_owned(), loc.to_owned(), varmap)?,
-                    self.check_expr(*ex1.to_owned(), loc.to_owned(), varmap)?,
+                    self.check_expr(*ex0, loc.to_owned(), varmap),
+                    self.check_expr(*ex1, loc.to_owned(), varmap),
                 ) {
+                    // one side already had an error
+                    (_, Type::Unknown, _) | (_, _, Type::Unknown) => Type::Unknown,
                     (
                         Operator::Add
                         | Operator::Sub
@@ -108,222 +187,466 @@ impl TypeChecker {
                     (Operator::BAnd | Operator::BOr | Operator::BXor, Type::Bool, Type::Bool) => {
                         Type::Bool
                     }
+                    // Shl is not implemented for f64 either
+                    (Operator::Shl | Operator::Shr, Type::Int, Type::Int) => Type::Int,
+                    (Operator::Pow, Type::Int, Type::Int) => Type::Int,
+                    (Operator::Pow, Type::Dcml, Type::Dcml) => Type::Dcml,
                     (op, t0, t1) => {
-                        let err = self.err_binop(&loc, op, t0, t1);
-                        self.add_err(err.clone());
-                        return Err(err);
+                        let err = self.err(
+                            &loc,
+                            ErrorType::BadBinOp {
+                                op: op.to_owned(),
+                                left: t0,
+                                right: t1,
+                            },
+                        );
+                        self.add_err(err);
+                        Type::Unknown
                     }
-                },
-            ),
-            ExprAST::Call(ref s, ref exprs) => {
-                let (inputs, output) = self.funcmap.get(s).unwrap().to_owned();
+                }
+            }
+            // a user function with a builtin's name has been reported already, its
+            // calls use the user's signature so they don't give a second error
+            ExprAST::Call(ref s, ref exprs, _)
+                if is_builtin(s) && !self.funcmap.contains_key(s) =>
+            {
+                // builtins are looked at before user functions
+                let mut arg_types = Vec::new();
+                for expr in exprs {
+                    arg_types.push(self.check_expr(expr.expr.to_owned(), loc.to_owned(), varmap));
+                }
+                if arg_types.contains(&Type::Unknown) {
+                    return Type::Unknown;
+                }
+                let Some(id) = find_builtin(s, &arg_types) else {
+                    let err = self.err(
+                        &loc,
+                        ErrorType::BadBuiltinArgs {
+                            name: s.to_owned(),
+                            args: arg_types,
+                        },
+                    );
+                    self.add_err(err);
+                    return Type::Unknown;
+                };
+                BUILTINS[id as usize].ret_type.to_owned()
+            }
+            ExprAST::Call(ref s, ref exprs, ref call_loc) => {
+                // a user function that reuses a host function's name has been reported
+                // already, so its own signature is used to not report its calls again
+                let sig = match self.funcmap.get(s) {
+                    Some(sig) => Some(sig.to_owned()),
+                    None => match self.host.find(s) {
+                        Some((_, host_func)) => {
+                            Some((host_func.params.to_owned(), host_func.ret_type.to_owned()))
+                        }
+                        None => {
+                            let err = self.err_missing_func(call_loc, s);
+                            self.add_err(err);
+                            None
+                        }
+                    },
+                };
+                // the arguments are checked even if the function doesn't exist
+                let arg_types = exprs
+                    .iter()
+                    .map(|expr| self.check_expr(expr.expr.to_owned(), loc.to_owned(), varmap))
+                    .collect::<Vec<Type>>();
+                let Some((inputs, output)) = sig else {
+                    return Type::Unknown;
+                };
                 if inputs.len() != exprs.len() {
                     let err = self.err(
                         &loc,
-                        &format!(
-                            "Expected {} arguments in call to function '{}', got {} args",
-                            inputs.len(),
-                            s,
-                            exprs.len()
-                        ),
+                        ErrorType::WrongArgCount {
+                            name: s.to_owned(),
+                            expected: inputs.len(),
+                            found: exprs.len(),
+                        },
                     );
-                    self.add_err(err.clone());
-                    return Err(err);
+                    self.add_err(err);
+                    // the function still gives back what it says it does
+                    return output;
                 }
-                for (expect_in, actual_in) in inputs.clone().iter().zip(exprs.iter()) {
-                    let actual_in =
-                        self.check_expr(actual_in.expr.to_owned(), loc.to_owned(), varmap)?;
-                    if discriminant(expect_in) != discriminant(&actual_in) {
-                        let err = self.err_func(&loc, s, &expect_in, &actual_in);
-                        self.add_err(err.clone());
-                        return Err(err);
+                for (expect_in, actual_in) in inputs.iter().zip(arg_types.iter()) {
+                    if *actual_in != Type::Unknown
+                        && discriminant(expect_in) != discriminant(actual_in)
+                    {
+                        let err = self.err(
+                            &loc,
+                            ErrorType::WrongArgType {
+                                name: s.to_owned(),
+                                expected: expect_in.to_owned(),
+                                found: actual_in.to_owned(),
+                            },
+                        );
+                        self.add_err(err);
                     }
                 }
-                Ok(output.to_owned())
+                output
             }
             ExprAST::Casted(ref datatype, expr) => {
-                let expr_type = self.check_expr(*expr, loc.to_owned(), &varmap)?;
-                Ok(match (expr_type, datatype) {
+                match (self.check_expr(*expr, loc.to_owned(), varmap), datatype) {
+                    (Type::Unknown, _) => Type::Unknown,
                     (Type::Int, Type::Dcml) => Type::Dcml,
                     (Type::Dcml, Type::Int) => Type::Int,
                     (Type::Bool, Type::Int) => Type::Int,
-                    (x, y) => {
+                    (from, to) => {
                         let err = self.err(
                             &loc,
-                            &format!("Cannot cast type '{:#?}' to type '{:#?}'", x, y,),
+                            ErrorType::BadCast {
+                                from,
+                                to: to.to_owned(),
+                            },
                         );
-                        self.add_err(err.clone());
-                        return Err(err);
+                        self.add_err(err);
+                        Type::Unknown
                     }
-                })
+                }
             }
             ExprAST::DotOp(dot_op, expr) => {
-                todo!()
-                //TODO when array type is actually implemented.
+                let arr_type = self.check_expr(*expr, loc.to_owned(), varmap);
+                let item_type = self.check_array(arr_type, &loc);
+                match dot_op {
+                    DotOp::Len => Type::Int,
+                    DotOp::Pop => Type::Void,
+                    DotOp::Push(val) => {
+                        let val_type = self.check_expr(*val, loc.to_owned(), varmap);
+                        if let Some(item_type) = item_type
+                            && val_type != Type::Unknown
+                            && val_type != item_type
+                        {
+                            let err = self.err(
+                                &loc,
+                                ErrorType::WrongArgType {
+                                    name: String::from("push"),
+                                    expected: item_type,
+                                    found: val_type,
+                                },
+                            );
+                            self.add_err(err);
+                        }
+                        Type::Void
+                    }
+                }
             }
             ExprAST::Indexed(to_be_indexed, index) => {
-                todo!()
-                //TODO when array type is fully implemented.
+                // how long the array is isn't known yet, but nothing is ever below 0
+                if let Some(Literal::Int(index)) = eval_const_expr(&index, &self.constmap)
+                    && index < 0
+                {
+                    let err = self.err(&loc, ErrorType::NegativeIndex { index });
+                    self.add_err(err);
+                }
+                let arr_type = self.check_expr(*to_be_indexed, loc.to_owned(), varmap);
+                let item_type = self.check_array(arr_type, &loc);
+                match self.check_expr(*index, loc.to_owned(), varmap) {
+                    Type::Int | Type::Unknown => (),
+                    found => {
+                        let err = self.err(&loc, ErrorType::IndexNotInt { found });
+                        self.add_err(err);
+                    }
+                }
+                item_type.unwrap_or(Type::Unknown)
             }
         }
     }
     fn check_statement(
         &mut self,
         statement: Statement,
-        varmap: &mut HashMap<String, Type>,
+        varmap: &mut VarMap,
         ret_type: &Type, // TODO ADD TYPE CHECK FOR RETURN TYPES
-    ) -> Result<(), CompileError> {
+    ) {
         match statement {
             Statement::Expr(expression) => {
-                self.check_expr(expression.expr, expression.loc, &varmap)?;
-                Ok(())
+                self.check_expr(expression.expr, expression.loc, varmap);
             }
             Statement::Decl(declaration) => {
-                let expr_ret_type =
-                    self.check_expr(declaration.val, declaration.val_loc, &varmap)?;
-                if discriminant(&declaration.typ) != discriminant(&expr_ret_type) {
-                    let err = CompileError {
-                        e_type: ErrorType::TypeError(format!(
-                            "Tried to set variable '{}' of type '{:#?}' to type of '{:#?}'",
-                            &declaration.ident, declaration.typ, expr_ret_type
-                        )),
-                        line: declaration.ident_loc.line,
-                        col: declaration.ident_loc.col,
-                    };
-                    self.errors.push(err.to_owned());
-                    return Err(err);
+                let expr_ret_type = self.check_expr(declaration.val, declaration.val_loc, varmap);
+                // `var` takes whatever type the value has
+                let decl_type = match declaration.typ {
+                    Some(typ) => typ,
+                    // like a void host function, there is nothing to take the type of
+                    None if expr_ret_type == Type::Void => {
+                        let err = self.err(
+                            &declaration.ident_loc,
+                            ErrorType::NoValue {
+                                name: declaration.ident.to_owned(),
+                            },
+                        );
+                        self.add_err(err);
+                        Type::Unknown
+                    }
+                    None => expr_ret_type.to_owned(),
+                };
+                if expr_ret_type != Type::Unknown
+                    && decl_type != Type::Unknown
+                    && discriminant(&decl_type) != discriminant(&expr_ret_type)
+                {
+                    let err = self.err(
+                        &declaration.ident_loc,
+                        ErrorType::WrongVarType {
+                            name: declaration.ident.to_owned(),
+                            expected: decl_type.to_owned(),
+                            found: expr_ret_type,
+                        },
+                    );
+                    self.add_err(err);
+                }
+                if self.consttypes.contains_key(&declaration.ident) {
+                    let err = self.err_const_name(&declaration.ident_loc, &declaration.ident);
+                    self.add_err(err);
+                    return;
                 }
                 if varmap.contains_key(&declaration.ident) {
                     let err = self.err(
                         &declaration.ident_loc,
-                        &format!(
-                            "Cannot declare the same named variable '{}' twice in the same scope (Shadowing is not allowed).",
-                            declaration.ident
-                        ),
+                        ErrorType::DuplicateVariable {
+                            name: declaration.ident,
+                        },
                     );
-                    self.add_err(err.to_owned());
-                    return Err(err);
+                    self.add_err(err);
+                    return;
                 };
 
-                varmap.insert(declaration.ident, declaration.typ);
-                Ok(())
+                let const_loc = if declaration.constant {
+                    Some(declaration.ident_loc)
+                } else {
+                    None
+                };
+                // the variable is declared even if its value had an error,
+                // so using it later doesn't give another error
+                varmap.insert(declaration.ident, (decl_type, const_loc));
             }
             Statement::Assign(assignment) => {
-                let expr_ret_type = self.check_expr(assignment.val, assignment.val_loc, &varmap)?;
-                let Some(actual_type) = varmap.get(&assignment.ident) else {
+                let expr_ret_type = self.check_expr(assignment.val, assignment.val_loc, varmap);
+                if !varmap.contains_key(&assignment.ident)
+                    && self.consttypes.contains_key(&assignment.ident)
+                {
                     let err = self.err(
                         &assignment.ident_loc,
-                        &format!(
-                            "Could not find variable '{}' in current scope",
-                            assignment.ident
-                        ),
+                        ErrorType::AssignToGlobalConst {
+                            name: assignment.ident,
+                        },
                     );
-                    self.add_err(err.to_owned());
-                    return Err(err);
+                    self.add_err(err);
+                    return;
+                }
+                let Some((actual_type, const_loc)) = varmap.get(&assignment.ident) else {
+                    let err =
+                        self.err_missing_var(&assignment.ident_loc, &assignment.ident, varmap);
+                    self.add_err(err);
+                    return;
                 };
-                if discriminant(actual_type) != discriminant(&expr_ret_type) {
-                    let err = CompileError {
-                        e_type: ErrorType::TypeError(format!(
-                            "Tried to set variable '{}' of type '{:#?}' to type of '{:#?}'",
-                            &assignment.ident, actual_type, expr_ret_type
-                        )),
-                        line: assignment.ident_loc.line,
-                        col: assignment.ident_loc.col,
-                    };
-                    self.errors.push(err.to_owned());
-                    return Err(err);
+                if let Some(const_loc) = const_loc {
+                    let label = fill(
+                        message_template("made_const_here"),
+                        &[("name", assignment.ident.to_owned())],
+                    );
+                    let err = self
+                        .err(
+                            &assignment.ident_loc,
+                            ErrorType::AssignToConst {
+                                name: assignment.ident.to_owned(),
+                                decl_line: const_loc.line,
+                                decl_col: const_loc.col,
+                            },
+                        )
+                        .with_label(const_loc, label);
+                    self.add_err(err);
+                    return;
+                }
+                if *actual_type != Type::Unknown
+                    && expr_ret_type != Type::Unknown
+                    && discriminant(actual_type) != discriminant(&expr_ret_type)
+                {
+                    let err = self.err(
+                        &assignment.ident_loc,
+                        ErrorType::WrongVarType {
+                            name: assignment.ident.to_owned(),
+                            expected: actual_type.to_owned(),
+                            found: expr_ret_type,
+                        },
+                    );
+                    self.add_err(err);
                 }
-
-                Ok(())
             }
             Statement::If(ifblock) => {
-                let condcheck = self.check_expr(ifblock.cond.expr, ifblock.cond.loc, &varmap);
-                let truecheck = self.check_block(ifblock.tcode, &varmap, ret_type);
-                let falsecheck = self.check_block(ifblock.ecode, &varmap, ret_type);
-                condcheck?;
-                truecheck?;
-                falsecheck
+                self.check_expr(ifblock.cond.expr, ifblock.cond.loc, varmap);
+                self.check_block(ifblock.tcode, varmap, ret_type);
+                self.check_block(ifblock.ecode, varmap, ret_type);
             }
             Statement::While(whileblock) => {
-                let condcheck = self.check_expr(whileblock.cond.expr, whileblock.cond.loc, &varmap);
-                let blockcheck = self.check_block(whileblock.code, &varmap, ret_type);
-                condcheck?;
-                blockcheck
+                self.check_expr(whileblock.cond.expr, whileblock.cond.loc, varmap);
+                self.check_block(whileblock.code, varmap, ret_type);
             }
             Statement::Return(returnblock) => {
                 let return_type =
-                    self.check_expr(returnblock.expr.expr, returnblock.expr.loc, &varmap)?;
-                if &return_type == ret_type {
-                    Ok(())
-                } else {
+                    self.check_expr(returnblock.expr.expr, returnblock.expr.loc, varmap);
+                if return_type != Type::Unknown && &return_type != ret_type {
                     let err = self.err(
                         &returnblock.loc,
-                        &format!(
-                            "Return statement is of type `{:#?}`, expected type `{:#?}`",
-                            return_type, ret_type
-                        ),
+                        ErrorType::WrongReturnType {
+                            expected: ret_type.to_owned(),
+                            found: return_type,
+                        },
                     );
-                    self.errors.push(err.to_owned());
-                    return Err(err);
+                    self.add_err(err);
                 }
             }
         }
     }
-    fn check_block(
-        &mut self,
-        block: Vec<Statement>,
-        varmap: &HashMap<String, Type>,
-        ret_type: &Type,
-    ) -> Result<(), CompileError> {
+    fn check_block(&mut self, block: Vec<Statement>, varmap: &VarMap, ret_type: &Type) {
         let mut varmap = varmap.to_owned();
         for statement in block {
-            self.check_statement(statement, &mut varmap, ret_type)?;
+            self.check_statement(statement, &mut varmap, ret_type);
         }
-        Ok(())
     }
-    fn check_func(&mut self, func: FunctionAst) -> Result<(), CompileError> {
+    // checks every statement, even after one has an error
+    fn check_func(&mut self, func: FunctionAst) {
+        self.func_decls = HashMap::new();
+        collect_decls(&func.code, &mut self.func_decls);
         let mut varmap = HashMap::new();
         for arg in func.params {
-            varmap.insert(arg.0, arg.1);
+            if varmap.contains_key(&arg.0) {
+                let err = self.err(
+                    &func.loc,
+                    ErrorType::DuplicateParam {
+                        func: func.name.to_owned(),
+                        name: arg.0,
+                    },
+                );
+                self.add_err(err);
+                continue;
+            }
+            if self.consttypes.contains_key(&arg.0) {
+                let err = self.err_const_name(&func.loc, &arg.0);
+                self.add_err(err);
+                continue;
+            }
+            varmap.insert(arg.0, (arg.1, None));
         }
         for statement in func.code {
-            self.check_statement(statement, &mut varmap, &func.ret_type)?;
+            self.check_statement(statement, &mut varmap, &func.ret_type);
         }
-        Ok(())
     }
-    fn err(&self, loc: &Loc, custom_message: &String) -> CompileError {
-        CompileError {
-            e_type: ErrorType::TypeError(custom_message.clone()),
-            line: loc.line,
-            col: loc.col,
+    // constants can only use literals and constants declared above them,
+    // and the value is checked the same way as one inside a function
+    fn check_global_const(&mut self, global: GlobalConst) {
+        if self.consttypes.contains_key(&global.ident) {
+            let err = self.err(
+                &global.ident_loc,
+                ErrorType::DuplicateConst { name: global.ident },
+            );
+            self.add_err(err);
+            return;
+        }
+        let errors_before = self.errors.len();
+        let found = self.check_expr(
+            global.val.to_owned(),
+            global.val_loc.to_owned(),
+            &VarMap::new(),
+        );
+        // the value already has its own error
+        if found == Type::Unknown || self.errors.len() > errors_before {
+            self.consttypes.insert(global.ident, Type::Unknown);
+            return;
+        }
+        if discriminant(&global.typ) != discriminant(&found) {
+            let err = self.err(
+                &global.ident_loc,
+                ErrorType::WrongConstType {
+                    name: global.ident.to_owned(),
+                    expected: global.typ,
+                    found,
+                },
+            );
+            self.add_err(err);
+            self.consttypes.insert(global.ident, Type::Unknown);
+            return;
         }
+        let Some(lit) = eval_const_expr(&global.val, &self.constmap) else {
+            let err = self.err(
+                &global.val_loc,
+                ErrorType::ConstNotKnown {
+                    name: global.ident.to_owned(),
+                },
+            );
+            self.add_err(err);
+            self.consttypes.insert(global.ident, Type::Unknown);
+            return;
+        };
+        self.consttypes.insert(global.ident.to_owned(), global.typ);
+        self.constmap.insert(global.ident, lit);
     }
-    fn err_func(
-        &self,
-        loc: &Loc,
-        func_name: &String,
-        expected: &Type,
-        actual: &Type,
-    ) -> CompileError {
-        CompileError {
-            e_type: ErrorType::TypeError(format!(
-                "Expected type '{:#?}', got type '{:#?}' in function '{}'",
-                expected, actual, func_name
-            )),
-            line: loc.line,
-            col: loc.col,
+    fn err_missing_var(&self, loc: &Loc, name: &String, varmap: &VarMap) -> CompileError {
+        let name = name.to_owned();
+        match self.func_decls.get(&name) {
+            Some(decl_loc) if (decl_loc.line, decl_loc.col) > (loc.line, loc.col) => self.err(
+                loc,
+                ErrorType::UsedBeforeDeclared {
+                    name,
+                    decl_line: decl_loc.line,
+                },
+            ),
+            Some(decl_loc) => self.err(
+                loc,
+                ErrorType::OutOfScope {
+                    name,
+                    decl_line: decl_loc.line,
+                },
+            ),
+            None => {
+                let in_scope = varmap.keys().chain(self.consttypes.keys());
+                let suggestions =
+                    suggest(&name, in_scope.map(|s| s.as_str()).chain(all_keywords()));
+                self.err(loc, ErrorType::UnknownVariable { name })
+                    .with_suggestions(suggestions)
+            }
         }
     }
-    fn err_binop(&self, loc: &Loc, op: &Operator, t0: Type, t1: Type) -> CompileError {
-        CompileError {
-            e_type: ErrorType::TypeError(format!(
-                "Cannot use binary operator '{:#?}' on values of type '{:#?}' and '{:#?}'",
-                op, t0, t1
-            )),
-            line: loc.line,
-            col: loc.col,
+    fn err_missing_func(&self, loc: &Loc, name: &String) -> CompileError {
+        let user_funcs = self.funcmap.keys().map(|s| s.as_str());
+        let builtins = BUILTINS.iter().map(|builtin| builtin.name);
+        let suggestions = suggest(
+            name,
+            user_funcs
+                .chain(builtins)
+                .chain(self.host.names())
+                .chain(all_keywords()),
+        );
+        self.err(
+            loc,
+            ErrorType::UnknownFunction {
+                name: name.to_owned(),
+            },
+        )
+        .with_suggestions(suggestions)
+    }
+    // the type of what is in the array, if it is one
+    fn check_array(&mut self, typ: Type, loc: &Loc) -> Option<Type> {
+        match typ {
+            Type::Array(item_type) => Some(*item_type),
+            Type::Unknown => None,
+            found => {
+                let err = self.err(loc, ErrorType::NotAnArray { found });
+                self.add_err(err);
+                None
+            }
         }
     }
+    fn err(&self, loc: &Loc, e_type: ErrorType) -> CompileError {
+        CompileError::at(e_type, loc)
+    }
+    fn err_const_name(&self, loc: &Loc, name: &String) -> CompileError {
+        self.err(
+            loc,
+            ErrorType::NameIsConst {
+                name: name.to_owned(),
+            },
+        )
+    }
+    // never reports the exact same error twice
     fn add_err(&mut self, error: CompileError) {
         for err in self.errors.iter() {
             if error.e_type == err.e_type && error.line == err.line && error.col == err.col {
@@ -333,7 +656,7 @@ impl TypeChecker {
         self.errors.push(error);
     }
     pub fn get_binop_type_panic(t0: Type, t1: Type, op: &Operator) -> Type {
-        let mut tycheck = TypeChecker::new(Vec::new());
+        let mut tycheck = TypeChecker::new(Vec::new(), Vec::new());
         let bogus0 = match t0 {
             Type::Int => ExprAST::Lit(Literal::Int(0)),
             Type::Dcml => ExprAST::Lit(Literal::Dcml(0.0)),
@@ -349,10 +672,266 @@ impl TypeChecker {
             _ => panic!(),
         };
         let binop = ExprAST::BinOp(op.to_owned(), Box::new(bogus0), Box::new(bogus1));
-        let datatype = tycheck.check_expr(binop, Loc::new(0, 0), &HashMap::new());
-        if let Ok(res) = datatype {
-            return res;
+        match tycheck.check_expr(binop, Loc::new(0, 0), &HashMap::new()) {
+            Type::Unknown => panic!(),
+            res => res,
+        }
+    }
+}
+
+// finds every declaration in a block, including the ones in inner blocks
+fn collect_decls(block: &Vec<Statement>, decls: &mut HashMap<String, Loc>) {
+    for statement in block {
+        match statement {
+            Statement::Decl(decl) => {
+                decls.entry(decl.ident.to_owned()).or_insert(decl.ident_loc);
+            }
+            Statement::If(ifblock) => {
+                collect_decls(&ifblock.tcode, decls);
+                collect_decls(&ifblock.ecode, decls);
+            }
+            Statement::While(whileblock) => collect_decls(&whileblock.code, decls),
+            Statement::Expr(_) | Statement::Assign(_) | Statement::Return(_) => (),
         }
-        panic!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::{
+        DEFAULT_SEED,
+        errors::ErrorType,
+        host::HostRegistry,
+        keywords::ENGLISH,
+        run_code_with_host,
+        test_util::{codes, errors, run},
+        tokens::Type,
+    };
+
+    #[test]
+    fn negative_constant_index() {
+        let code = "const int LAST = 0 - 1;
+fun main() -> int {
+    int x = 5;
+    return x[LAST];
+}";
+        assert_eq!(codes(code), vec!["T0011", "T0012"]);
+    }
+
+    #[test]
+    fn index_has_to_be_int() {
+        let code = "fun main() -> int {
+    int x = 5;
+    return x[true];
+}";
+        assert_eq!(codes(code), vec!["T0012", "T0013"]);
+    }
+
+    #[test]
+    fn bad_const_is_reported_once() {
+        let code = "const int Q = 1 + true;
+fun main() -> int {
+    return Q + Q;
+}";
+        assert_eq!(codes(code), vec!["T0001"]);
+    }
+
+    #[test]
+    fn const_value_is_type_checked() {
+        let code = "const bool Q = 1 == 1.0;
+fun main() -> int {
+    if Q { return 1; }
+    return 0;
+}";
+        assert_eq!(codes(code), vec!["T0001"]);
+    }
+
+    #[test]
+    fn dot_op_needs_an_array() {
+        let code = "fun main() -> int {
+    int x = 5;
+    dcml half = .5;
+    return x.len;
+}";
+        assert_eq!(codes(code), vec!["T0012"]);
+    }
+
+    #[test]
+    fn shifts_need_ints() {
+        let code = "fun main() -> int {
+    dcml x = 1.5 << 2.0;
+    int y = 1 >> 2.0;
+    return 0;
+}";
+        assert_eq!(codes(code), vec!["T0001", "T0001"]);
+    }
+
+    #[test]
+    fn assign_to_const_local() {
+        let code = "fun main() -> int {
+    const int x = 1;
+    x = 2;
+    return x;
+}";
+        let errors = errors(code);
+        assert_eq!(errors.len(), 1);
+        let err = &errors[0];
+        assert_eq!(err.e_type.code(), "V0010");
+        assert_eq!((err.line, err.col), (3, 5));
+        let ErrorType::AssignToConst {
+            decl_line,
+            decl_col,
+            ..
+        } = err.e_type
+        else {
+            panic!("{:?}", err.e_type);
+        };
+        assert_eq!((decl_line, decl_col), (2, 15));
+        assert_eq!((err.labels[0].0.line, err.labels[0].0.col), (2, 15));
+    }
+
+    #[test]
+    fn assign_to_global_const() {
+        let code = "const int LIVES = 3;
+fun main() -> int {
+    int left = LIVES;
+    if left > 0 {
+        LIVES = 2;
+    }
+    return left;
+}";
+        let errors = errors(code);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].e_type.code(), "V0009");
+        assert_eq!((errors[0].line, errors[0].col), (5, 9));
+    }
+
+    #[test]
+    fn const_locals_are_read_in_their_own_scope() {
+        let code = "fun main() -> int {
+    const int base = 10;
+    int total = 0;
+    if total == 0 {
+        const int x = base + 5;
+        total = total + x;
+    }
+    int x = 1;
+    x = x + 1;
+    while total < 20 {
+        # made again every time around
+        const int step = x;
+        if true { total = total + step; }
+    }
+    return total * base + x;
+}";
+        assert_eq!(run(code), 212);
+    }
+
+    #[test]
+    fn var_takes_the_type_of_its_value() {
+        let code = "fun twice(int n) -> int { return n * 2; }
+fun main() -> int {
+    var count = 3;
+    var half = 0.5;
+    var big = count > 2;
+    var doubled = twice(count);
+    if big { return doubled + ((half * 4.0) become int); }
+    return 0;
+}";
+        assert_eq!(run(code), 8);
+    }
+
+    #[test]
+    fn var_keeps_its_type() {
+        let code = "fun twice(int n) -> int { return n * 2; }
+fun main() -> int {
+    var count = 3;
+    var half = 0.5;
+    var big = true;
+    var doubled = twice(count);
+    count = 1.5;
+    half = 1;
+    big = 0;
+    doubled = false;
+    return 0;
+}";
+        let errors = errors(code);
+        let expected: Vec<(Type, Type)> = errors
+            .iter()
+            .map(|err| match &err.e_type {
+                ErrorType::WrongVarType {
+                    expected, found, ..
+                } => (expected.to_owned(), found.to_owned()),
+                other => panic!("{:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            expected,
+            vec![
+                (Type::Int, Type::Dcml),
+                (Type::Dcml, Type::Int),
+                (Type::Bool, Type::Int),
+                (Type::Int, Type::Bool),
+            ]
+        );
+    }
+
+    #[test]
+    fn var_of_a_void_host_function() {
+        let mut host = HostRegistry::new();
+        host.register_void("voidHost", Vec::new(), |_| Ok(()))
+            .unwrap();
+        let code = "fun main() -> int {
+    var nothing = voidHost();
+    nothing = 5;
+    return nothing;
+}";
+        let errors = run_code_with_host(code.to_owned(), &ENGLISH, DEFAULT_SEED, host).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].e_type,
+            ErrorType::NoValue {
+                name: "nothing".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn var_with_a_bad_value_gives_one_error() {
+        let code = "fun main() -> int {
+    var broken = 1 + true;
+    int x = broken * 2;
+    broken = 2.5;
+    if broken { return broken; }
+    return x;
+}";
+        assert_eq!(codes(code), vec!["T0001"]);
+    }
+
+    #[test]
+    fn builtin_name_is_reported_once() {
+        let code = "fun max(int a, int b, int c) -> int {
+    return a;
+}
+fun main() -> int {
+    return max(1, 2, 3) + max(4, 5, 6);
+}";
+        assert_eq!(codes(code), vec!["V0011"]);
+    }
+
+    #[test]
+    fn host_name_is_reported_once() {
+        let mut host = HostRegistry::new();
+        host.register("twice", vec![Type::Int], Type::Int, |args| Ok(args[0]))
+            .unwrap();
+        let code = "fun twice(int a, int b) -> int {
+    return a + b;
+}
+fun main() -> int {
+    return twice(1, 2);
+}";
+        let errors = run_code_with_host(code.to_owned(), &ENGLISH, DEFAULT_SEED, host).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].e_type.code(), "V0012");
     }
 }
@@ -5,6 +5,10 @@ use std::thread;
 use std::time::Duration;
 
 use super::array::Array;
+use super::asm::Cmp;
+use super::builtins::{BUILTINS, Rng, call_builtin};
+use super::errors::ErrorType;
+use super::host::HostRegistry;
 use super::tokens::Type;
 use super::wrapped_val::WrappedVal;
 
@@ -38,6 +42,14 @@ const ARRPOP_NUM: u8 = 26;
 const ARRPUSH_NUM: u8 = 27;
 const FREEARR_NUM: u8 = 28;
 const ARRIND_NUM: u8 = 29;
+const SHL_NUM: u8 = 30;
+const SHR_NUM: u8 = 31;
+const POW_NUM: u8 = 32;
+const CALLNATIVE_NUM: u8 = 33;
+const CALLHOST_NUM: u8 = 34;
+const NE_NUM: u8 = 35;
+const MOVPOP_NUM: u8 = 36;
+const JMPIFFALSE_NUM: u8 = 37;
 
 // Constant identifiers for types
 const INT_NUM: u8 = 1;
@@ -48,42 +60,67 @@ const CALLSTACK_NUM: u8 = 5;
 const ARRAY_NUM: u8 = 6;
 
 pub struct VM {
+    // the index of the next op, not a byte address
     ip: usize,
+    // where the running function's args and locals start in the stack
+    frame: usize,
     consts: Vec<u8>,
-    inst: Vec<u8>,
+    ops: Vec<Op>,
     stack: Vec<u8>,
     pool: Vec<String>,
     array_stack: Vec<Array>,
+    rng: Rng,
+    host: HostRegistry,
+    // how many instructions have run, to see how much optimizing helps
+    executed: u64,
 }
 impl VM {
     pub fn new(pool: Vec<String>, consts: Vec<u8>, inst: Vec<u8>) -> Self {
-        VM {
+        let mut vm = VM {
             ip: 0,
+            frame: 0,
             consts,
-            inst,
+            ops: Vec::new(),
             pool,
             stack: Vec::new(),
             array_stack: Vec::new(),
-        }
+            rng: Rng::new(Rng::DEFAULT_SEED),
+            host: HostRegistry::new(),
+            executed: 0,
+        };
+        vm.ops = vm.decode(&inst);
+        vm
+    }
+    // has to be the same registry the code was compiled with
+    pub fn set_host(&mut self, host: HostRegistry) {
+        self.host = host;
+    }
+    // random() always gives the same numbers for the same seed
+    pub fn seed_random(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
     }
-    pub fn execute_order_66(&mut self) -> i32 {
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+    pub fn execute_order_66(&mut self) -> Result<i32, ErrorType> {
         loop {
-            if let ProgState::Halt(x) = self.eval_inst() {
-                break x;
+            match self.eval_inst() {
+                ProgState::Halt(x) => break Ok(x),
+                ProgState::Crash(e_type) => break Err(e_type),
+                ProgState::Running => (),
             }
             // println!("Whole Stack: {:#?}", self.get_entire_stack_wrapped());
             // println!("New IP: {}", self.ip);
         }
     }
-    pub fn timed_run(mut self, duration: Duration, interval: Duration) -> Option<i32> {
+    pub fn timed_run(
+        mut self,
+        duration: Duration,
+        interval: Duration,
+    ) -> Option<Result<i32, ErrorType>> {
         let (tx, rx) = mpsc::channel();
         thread::spawn(move || {
-            loop {
-                if let ProgState::Halt(x) = self.eval_inst() {
-                    tx.send(x);
-                    break;
-                }
-            }
+            let _ = tx.send(self.execute_order_66());
         });
         let max_millis = duration.as_millis();
         let int_millis = interval.as_millis();
@@ -103,49 +140,116 @@ impl VM {
             return None;
         }
     }
-    pub fn debug_eval(&mut self) -> i32 {
+    pub fn debug_eval(&mut self) -> Result<i32, ErrorType> {
         println!("All Consts: {:#?}", self.get_all_consts());
         loop {
-            if let ProgState::Halt(x) = self.eval_inst() {
-                break x;
+            match self.eval_inst() {
+                ProgState::Halt(x) => break Ok(x),
+                ProgState::Crash(e_type) => break Err(e_type),
+                ProgState::Running => (),
             }
             println!("Whole Stack: {:#?}", self.get_entire_stack_wrapped());
+            println!("Frame: {:#?}", self.get_frame_wrapped());
             println!("New IP: {}", self.ip);
         }
     }
+    // works out every instruction once, so running one doesn't have to read bytes.
+    // jumps and calls point at byte addresses, which turn into indexes in the ops
+    fn decode(&self, inst: &[u8]) -> Vec<Op> {
+        let mut op_index = vec![0; inst.len() + 1];
+        let mut ip = 0;
+        let mut count = 0;
+        while ip < inst.len() {
+            op_index[ip] = count;
+            ip += get_inst_size(inst[ip]);
+            count += 1;
+        }
+        op_index[ip] = count;
+        let u16_at =
+            |at: usize| u16::from_le_bytes(inst[at..(at + size_of::<u16>())].try_into().unwrap());
+        let target_at = |at: usize| {
+            op_index
+                [u32::from_le_bytes(inst[at..(at + size_of::<u32>())].try_into().unwrap()) as usize]
+        };
+        let mut ops = Vec::with_capacity(count);
+        let mut ip = 0;
+        while ip < inst.len() {
+            let st = ip + 1; // easy shorthand to be the start of the data of the inst
+            ops.push(match inst[ip] {
+                RET_NUM => Op::Ret,
+                PUSH_NUM => {
+                    const PUSH_FROM_FRAME: u8 = 0;
+                    const PUSH_FROM_CONSTS: u8 = 1;
+                    match inst[st] {
+                        PUSH_FROM_FRAME => Op::PushLocal(u16_at(st + 1)),
+                        PUSH_FROM_CONSTS => Op::PushConst(self.get_const_wrapped(u16_at(st + 1))),
+                        _ => unreachable!(),
+                    }
+                }
+                POP_NUM => Op::Pop,
+                MOV_NUM => Op::Mov(u16_at(st)),
+                MOVPOP_NUM => Op::MovPop(u16_at(st)),
+                ADD_NUM => Op::Add,
+                SUB_NUM => Op::Sub,
+                MUL_NUM => Op::Mul,
+                DIV_NUM => Op::Div,
+                MOD_NUM => Op::Mod,
+                AND_NUM => Op::And,
+                OR_NUM => Op::Or,
+                NOT_NUM => Op::Not,
+                XOR_NUM => Op::Xor,
+                EQ_NUM | NE_NUM | L_NUM | LE_NUM | G_NUM | GE_NUM => {
+                    Op::Compare(Cmp::from_num(inst[ip]).unwrap())
+                }
+                SHL_NUM => Op::Shl,
+                SHR_NUM => Op::Shr,
+                POW_NUM => Op::Pow,
+                JMP_NUM => Op::Jmp(target_at(st)),
+                JZ_NUM => Op::Jz(target_at(st)),
+                JNZ_NUM => Op::Jnz(target_at(st)),
+                // the comparison is the number of the instruction that does it alone
+                JMPIFFALSE_NUM => {
+                    Op::JmpIfFalse(Cmp::from_num(inst[st]).unwrap(), target_at(st + 1))
+                }
+                CALL_NUM => Op::Call(target_at(st)),
+                CALLNATIVE_NUM => Op::CallNative(u16_at(st)),
+                CALLHOST_NUM => Op::CallHost(u16_at(st)),
+                FUN_NUM => Op::Fun(u16_at(st)),
+                CAST_NUM => Op::Cast(inst[st]),
+                ARRLEN_NUM => Op::ArrLen,
+                ARRPOP_NUM => Op::ArrPop,
+                ARRPUSH_NUM => Op::ArrPush,
+                FREEARR_NUM => Op::FreeArr,
+                ARRIND_NUM => Op::ArrInd,
+                _ => unreachable!(),
+            });
+            ip += get_inst_size(inst[ip]);
+        }
+        ops
+    }
     fn eval_inst(&mut self) -> ProgState {
-        let ip = self.ip;
-        let len = get_inst_size(self.inst[ip]);
-        self.ip += len;
-        let st = ip + 1; // easy shorthand to be the start of the data of the inst
-        match self.inst[ip] {
-            RET_NUM => {
-                let how_much_bytes_to_pop = u16::from_le_bytes(
-                    self.inst[(st)..(st + size_of::<u16>())].try_into().unwrap(),
-                );
+        let op = self.ops[self.ip];
+        self.executed += 1;
+        self.ip += 1;
+        match op {
+            Op::Ret => {
                 let ret_val = self.pop_stack_top_wrapped();
-                // println!("Function returned: {:#?}", ret_val);
-                for _ in 0..(how_much_bytes_to_pop - ret_val.type_enum().size() as u16) {
-                    self.stack.pop();
-                }
-                // after this, there should be a number on the stack
-                // that will point to where the function was called from.
-                // It will have a unique identifier, and if that identifier is not
-                // found, then we are in main, and return with the top thing on the stack.
-                // println!(
-                //     "RIGHT BEFORE RETURN WHOLE STACK! {:#?}",
-                //     self.get_entire_stack_wrapped()
-                // );
-                if self.stack.len() > 0 && self.stack[self.stack.len() - 1] == CALLSTACK_NUM {
-                    self.ip = u32::from_le_bytes(
-                        self.stack
-                            [(self.stack.len() - 1 - size_of::<u32>())..(self.stack.len() - 1)]
-                            .try_into()
-                            .unwrap(),
-                    ) as usize;
-                    for _ in 0..(size_of::<u32>() + 1) {
-                        self.stack.pop();
-                    }
+                // the args and locals all go at once
+                self.stack.truncate(self.frame);
+                // under the frame is where the function was called from and the
+                // frame of the caller. If they aren't there, then we are in main,
+                // and return with the value.
+                if self.stack.last() == Some(&CALLSTACK_NUM) {
+                    let callstack_size = get_type_size(CALLSTACK_NUM);
+                    let (WrappedVal::CallStack(ip), WrappedVal::CallStack(frame)) = (
+                        self.wrap_val_at(self.frame - callstack_size),
+                        self.wrap_val_at(self.frame),
+                    ) else {
+                        unreachable!();
+                    };
+                    self.stack.truncate(self.frame - 2 * callstack_size);
+                    self.frame = frame as usize;
+                    self.ip = ip as usize;
                     self.push_wrapped(ret_val);
                 } else {
                     let WrappedVal::Int(ret_val) = ret_val else {
@@ -154,178 +258,157 @@ impl VM {
                     return ProgState::Halt(ret_val);
                 }
             }
-            PUSH_NUM => {
-                const PUSH_FROM_STACK: u8 = 0;
-                const PUSH_FROM_CONSTS: u8 = 1;
-                match self.inst[st] {
-                    PUSH_FROM_STACK => {
-                        let offset = u16::from_le_bytes(
-                            self.inst[(st + 1)..(st + 1 + size_of::<u16>())]
-                                .try_into()
-                                .unwrap(),
-                        );
-                        let wrapped_val = self.wrap_stack_val(offset); // failing here
-                        self.push_wrapped(wrapped_val);
-                    }
-                    PUSH_FROM_CONSTS => {
-                        let index = u16::from_le_bytes(
-                            self.inst[(st + 1)..(st + 1 + size_of::<u16>())]
-                                .try_into()
-                                .unwrap(),
-                        );
-                        let wrapped_val = self.get_const_wrapped(index);
-                        self.push_wrapped(wrapped_val);
-                    }
-                    _ => unreachable!(),
-                }
-            }
-            POP_NUM => {
-                let size = match self.stack[self.stack.len() - 1] {
-                    INT_NUM => 4,
-                    DCML_NUM => 8,
-                    BOOL_NUM => 1,
-                    STRING_NUM => 2,
-                    CALLSTACK_NUM => 4,
-                    _ => unreachable!(),
-                } + 1;
-                for _ in 0..size {
-                    self.stack.pop();
-                }
+            Op::PushLocal(index) => {
+                let wrapped_val = self.wrap_val_at(self.frame + index as usize);
+                self.push_wrapped(wrapped_val);
             }
-            MOV_NUM => {
-                let offset = u16::from_le_bytes(
-                    self.inst[(st)..(st + size_of::<u16>())].try_into().unwrap(),
-                );
-                self.mutate_var(offset);
+            Op::PushConst(wrapped_val) => self.push_wrapped(wrapped_val),
+            Op::Pop => self.drop_top(),
+            Op::Mov(index) => self.mutate_var(self.frame + index as usize),
+            Op::MovPop(index) => {
+                self.mutate_var(self.frame + index as usize);
+                self.drop_top();
             }
-            ADD_NUM => {
+            Op::Add => {
                 let right = self.pop_stack_top_wrapped();
                 let left = self.pop_stack_top_wrapped();
-                let ans = left + right;
-                self.push_wrapped(ans);
+                self.push_wrapped(left + right);
             }
-            SUB_NUM => {
+            Op::Sub => {
                 let right = self.pop_stack_top_wrapped();
                 let left = self.pop_stack_top_wrapped();
-                let ans = left - right;
-                self.push_wrapped(ans);
+                self.push_wrapped(left - right);
             }
-            MUL_NUM => {
+            Op::Mul => {
                 let right = self.pop_stack_top_wrapped();
                 let left = self.pop_stack_top_wrapped();
-                let ans = left * right;
-                self.push_wrapped(ans);
+                self.push_wrapped(left * right);
             }
-            DIV_NUM => {
+            Op::Div => {
                 let right = self.pop_stack_top_wrapped();
                 let left = self.pop_stack_top_wrapped();
-                let ans = left / right;
-                self.push_wrapped(ans);
+                self.push_wrapped(left / right);
             }
-            MOD_NUM => {
+            Op::Mod => {
                 let right = self.pop_stack_top_wrapped();
                 let left = self.pop_stack_top_wrapped();
-                let ans = left % right;
-                self.push_wrapped(ans);
+                self.push_wrapped(left % right);
             }
-            AND_NUM => {
+            Op::And => {
                 let right = self.pop_stack_top_wrapped();
                 let left = self.pop_stack_top_wrapped();
-                let ans = left & right;
-                self.push_wrapped(ans);
+                self.push_wrapped(left & right);
             }
-            OR_NUM => {
+            Op::Or => {
                 let right = self.pop_stack_top_wrapped();
                 let left = self.pop_stack_top_wrapped();
-                let ans = left | right;
-                self.push_wrapped(ans);
+                self.push_wrapped(left | right);
             }
-            XOR_NUM => {
-                let right = self.pop_stack_top_wrapped();
-                let left = self.pop_stack_top_wrapped();
-                let ans = left ^ right;
-                self.push_wrapped(ans);
+            Op::Not => {
+                let val = self.pop_stack_top_wrapped();
+                self.push_wrapped(!val);
             }
-            EQ_NUM => {
+            Op::Xor => {
                 let right = self.pop_stack_top_wrapped();
                 let left = self.pop_stack_top_wrapped();
-                let ans = left == right;
-                self.push_wrapped(WrappedVal::Bool(ans));
+                self.push_wrapped(left ^ right);
             }
-            L_NUM => {
+            Op::Compare(cmp) => {
                 let right = self.pop_stack_top_wrapped();
                 let left = self.pop_stack_top_wrapped();
-                let ans = left < right;
+                let ans = compare(cmp, left, right);
                 self.push_wrapped(WrappedVal::Bool(ans));
             }
-            LE_NUM => {
+            Op::Shl => {
                 let right = self.pop_stack_top_wrapped();
                 let left = self.pop_stack_top_wrapped();
-                let ans = left <= right;
-                self.push_wrapped(WrappedVal::Bool(ans));
+                match left.checked_shl(right) {
+                    Ok(ans) => self.push_wrapped(ans),
+                    Err(msg) => return ProgState::Crash(msg),
+                }
             }
-            G_NUM => {
+            Op::Shr => {
                 let right = self.pop_stack_top_wrapped();
                 let left = self.pop_stack_top_wrapped();
-                let ans = left > right;
-                self.push_wrapped(WrappedVal::Bool(ans));
+                match left.checked_shr(right) {
+                    Ok(ans) => self.push_wrapped(ans),
+                    Err(msg) => return ProgState::Crash(msg),
+                }
             }
-            GE_NUM => {
+            Op::Pow => {
                 let right = self.pop_stack_top_wrapped();
                 let left = self.pop_stack_top_wrapped();
-                let ans = left >= right;
-                self.push_wrapped(WrappedVal::Bool(ans));
+                match left.checked_pow(right) {
+                    Ok(ans) => self.push_wrapped(ans),
+                    Err(msg) => return ProgState::Crash(msg),
+                }
             }
             // FINALLY DONE WITH OPS!!!
-            JMP_NUM => {
-                let jump_to =
-                    u32::from_le_bytes(self.inst[st..(st + size_of::<u32>())].try_into().unwrap());
-                self.ip = jump_to as usize;
-            }
-            JZ_NUM => {
+            Op::Jmp(jump_to) => self.ip = jump_to,
+            Op::Jz(jump_to) => {
                 if let WrappedVal::Bool(true) = self.pop_stack_top_wrapped() {
-                    let jump_to = u32::from_le_bytes(
-                        self.inst[st..(st + size_of::<u32>())].try_into().unwrap(),
-                    );
-                    self.ip = jump_to as usize;
+                    self.ip = jump_to;
                 }
             }
-            JNZ_NUM => {
+            Op::Jnz(jump_to) => {
                 if let WrappedVal::Bool(false) = self.pop_stack_top_wrapped() {
-                    let jump_to = u32::from_le_bytes(
-                        self.inst[st..(st + size_of::<u32>())].try_into().unwrap(),
-                    );
-                    self.ip = jump_to as usize;
+                    self.ip = jump_to;
                 }
             }
-            CALL_NUM => {
-                let callstack = self.ip as u32;
-                self.stack.extend_from_slice(&callstack.to_le_bytes());
-                self.stack.push(CALLSTACK_NUM);
-                let jump_to =
-                    u32::from_le_bytes(self.inst[st..(st + size_of::<u32>())].try_into().unwrap());
-                self.ip = jump_to as usize;
+            Op::JmpIfFalse(cmp, jump_to) => {
+                let right = self.pop_stack_top_wrapped();
+                let left = self.pop_stack_top_wrapped();
+                if !compare(cmp, left, right) {
+                    self.ip = jump_to;
+                }
+            }
+            Op::Call(jump_to) => {
+                // where to come back to, and the frame to go back to
+                self.push_wrapped(WrappedVal::CallStack(self.ip as u32));
+                self.push_wrapped(WrappedVal::CallStack(self.frame as u32));
+                self.ip = jump_to;
+            }
+            Op::CallNative(id) => {
+                let mut args = Vec::new();
+                for _ in 0..BUILTINS[id as usize].params.len() {
+                    args.push(self.pop_stack_top_wrapped());
+                }
+                args.reverse();
+                match call_builtin(id, args, &mut self.rng) {
+                    Ok(ans) => self.push_wrapped(ans),
+                    Err(msg) => return ProgState::Crash(msg),
+                }
+            }
+            Op::CallHost(id) => {
+                // borrowed each time instead of cloned, calls happen inside loops
+                let mut args = Vec::new();
+                for _ in 0..self.host.get(id).params.len() {
+                    args.push(self.pop_stack_top_wrapped());
+                }
+                args.reverse();
+                match self.host.get(id).call(args) {
+                    Ok(Some(ans)) => self.push_wrapped(ans),
+                    // void functions leave nothing on the stack
+                    Ok(None) => (),
+                    Err(msg) => return ProgState::Crash(msg),
+                }
             }
-            FUN_NUM => {
+            Op::Fun(arg_amount) => {
                 let is_main = self.stack.len() == 0;
 
                 if !is_main {
-                    let callstack = self.pop_stack_top_wrapped();
-                    let mut argvec = Vec::new();
-                    let arg_amount = u16::from_le_bytes(
-                        self.inst[st..(st + size_of::<u16>())].try_into().unwrap(),
-                    );
+                    // the call stack goes under the args, so the new frame
+                    // is just the args and then the locals
+                    let call_size = 2 * get_type_size(CALLSTACK_NUM);
+                    let mut start = self.stack.len() - call_size;
                     for _ in 0..arg_amount {
-                        argvec.push(self.pop_stack_top_wrapped());
-                    }
-                    self.push_wrapped(callstack);
-                    for wrap in argvec.into_iter().rev() {
-                        self.push_wrapped(wrap);
+                        start -= get_type_size(self.stack[start - 1]);
                     }
+                    self.stack[start..].rotate_right(call_size);
+                    self.frame = start + call_size;
                 }
             }
-            CAST_NUM => {
-                let to_type = self.inst[st];
+            Op::Cast(to_type) => {
                 let val = self.pop_stack_top_wrapped();
                 let new_val = match val {
                     WrappedVal::Int(int) => {
@@ -353,7 +436,7 @@ impl VM {
                 };
                 self.push_wrapped(new_val);
             }
-            ARRLEN_NUM => {
+            Op::ArrLen => {
                 let arr = self.pop_stack_top_wrapped();
                 let WrappedVal::Array(arr_ind) = arr else {
                     panic!()
@@ -361,7 +444,7 @@ impl VM {
                 let arraylen = self.array_stack[arr_ind as usize].length();
                 self.push_wrapped(WrappedVal::Int(arraylen));
             }
-            ARRPOP_NUM => {
+            Op::ArrPop => {
                 let arr = self.wrap_stack_val(0);
                 let WrappedVal::Array(arr_ind) = arr else {
                     panic!()
@@ -369,7 +452,7 @@ impl VM {
                 let array = &mut self.array_stack[arr_ind as usize];
                 array.pop();
             }
-            ARRPUSH_NUM => {
+            Op::ArrPush => {
                 let var = self.pop_stack_top_wrapped();
                 let arr = self.wrap_stack_val(0);
                 let WrappedVal::Array(arr_ind) = arr else {
@@ -378,7 +461,7 @@ impl VM {
                 let array = &mut self.array_stack[arr_ind as usize];
                 array.push_wrap(var);
             }
-            ARRIND_NUM => {
+            Op::ArrInd => {
                 let WrappedVal::Int(index) = self.pop_stack_top_wrapped() else {
                     panic!()
                 };
@@ -389,15 +472,29 @@ impl VM {
                 let wrap_val = array.index(index);
                 self.push_wrapped(wrap_val);
             }
-            FREEARR_NUM => {
+            Op::FreeArr => {
                 self.array_stack.pop();
             }
-            _ => unreachable!(),
         }
         return ProgState::Running;
     }
-    fn mutate_var(&mut self, offset_from_top: u16) {
-        let var_ptr = self.stack.len() - 1 - offset_from_top as usize;
+    // pops the top value without looking at it
+    fn drop_top(&mut self) {
+        let size = match self.stack[self.stack.len() - 1] {
+            INT_NUM => 4,
+            DCML_NUM => 8,
+            BOOL_NUM => 1,
+            STRING_NUM => 2,
+            CALLSTACK_NUM => 4,
+            _ => unreachable!(),
+        } + 1;
+        for _ in 0..size {
+            self.stack.pop();
+        }
+    }
+    // sets the var that ends right before `end` to the top value
+    fn mutate_var(&mut self, end: usize) {
+        let var_ptr = end - 1;
         let var_type = self.stack[var_ptr];
         let new_ptr = self.stack.len() - 1;
         let new_type = self.stack[new_ptr];
@@ -464,50 +561,39 @@ impl VM {
         ans
     }
     fn wrap_stack_val(&self, offset_from_top: u16) -> WrappedVal {
-        let off = offset_from_top as usize + 1;
-        match self.stack[self.stack.len() - off] {
+        self.wrap_val_at(self.stack.len() - offset_from_top as usize)
+    }
+    // the value that ends right before `end` in the stack
+    fn wrap_val_at(&self, end: usize) -> WrappedVal {
+        let tag = end - 1;
+        match self.stack[tag] {
             INT_NUM => {
-                let num = i32::from_le_bytes(
-                    self.stack[self.stack.len() - off - size_of::<i32>()..self.stack.len() - off]
-                        .try_into()
-                        .unwrap(),
-                );
+                let num =
+                    i32::from_le_bytes(self.stack[tag - size_of::<i32>()..tag].try_into().unwrap());
                 WrappedVal::Int(num)
             }
             DCML_NUM => {
-                let float = f64::from_le_bytes(
-                    self.stack[self.stack.len() - off - size_of::<f64>()..self.stack.len() - off]
-                        .try_into()
-                        .unwrap(),
-                );
+                let float =
+                    f64::from_le_bytes(self.stack[tag - size_of::<f64>()..tag].try_into().unwrap());
                 WrappedVal::Dcml(float)
             }
             BOOL_NUM => {
-                let boolean = self.stack[self.stack.len() - off - 1] != 0;
+                let boolean = self.stack[tag - 1] != 0;
                 WrappedVal::Bool(boolean)
             }
             STRING_NUM => {
-                let string_ind = u16::from_le_bytes(
-                    self.stack[self.stack.len() - off - size_of::<u16>()..self.stack.len() - off]
-                        .try_into()
-                        .unwrap(),
-                );
+                let string_ind =
+                    u16::from_le_bytes(self.stack[tag - size_of::<u16>()..tag].try_into().unwrap());
                 WrappedVal::String(string_ind)
             }
             CALLSTACK_NUM => {
-                let new_ip = u32::from_le_bytes(
-                    self.stack[self.stack.len() - off - size_of::<u32>()..self.stack.len() - off]
-                        .try_into()
-                        .unwrap(),
-                );
+                let new_ip =
+                    u32::from_le_bytes(self.stack[tag - size_of::<u32>()..tag].try_into().unwrap());
                 WrappedVal::CallStack(new_ip)
             }
             ARRAY_NUM => {
-                let array_ind = u16::from_le_bytes(
-                    self.stack[self.stack.len() - off - size_of::<u16>()..self.stack.len() - off]
-                        .try_into()
-                        .unwrap(),
-                );
+                let array_ind =
+                    u16::from_le_bytes(self.stack[tag - size_of::<u16>()..tag].try_into().unwrap());
                 WrappedVal::Array(array_ind)
             }
             _ => unreachable!(),
@@ -559,6 +645,18 @@ impl VM {
         vals.reverse();
         vals
     }
+    // the args and locals of the function that is running right now, plus
+    // whatever it is in the middle of working out
+    fn get_frame_wrapped(&self) -> Vec<WrappedVal> {
+        let mut end = self.stack.len();
+        let mut vals = Vec::new();
+        while end > self.frame {
+            vals.push(self.wrap_val_at(end));
+            end -= get_type_size(self.stack[end - 1]);
+        }
+        vals.reverse();
+        vals
+    }
 
     fn get_all_consts(&self) -> Vec<(usize, WrappedVal)> {
         let mut byte_index = 0;
@@ -615,14 +713,66 @@ impl VM {
     }
 }
 
+// an instruction after it is decoded, with its data already read out
+#[derive(Clone, Copy)]
+enum Op {
+    Ret,
+    PushLocal(u16),
+    PushConst(WrappedVal),
+    Pop,
+    Mov(u16),
+    MovPop(u16),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    And,
+    Or,
+    Not,
+    Xor,
+    Compare(Cmp),
+    Shl,
+    Shr,
+    Pow,
+    // jumps and calls hold the index of the op they go to
+    Jmp(usize),
+    Jz(usize),
+    Jnz(usize),
+    JmpIfFalse(Cmp, usize),
+    Call(usize),
+    CallNative(u16),
+    CallHost(u16),
+    Fun(u16),
+    Cast(u8),
+    ArrLen,
+    ArrPop,
+    ArrPush,
+    FreeArr,
+    ArrInd,
+}
+
+fn compare(cmp: Cmp, left: WrappedVal, right: WrappedVal) -> bool {
+    match cmp {
+        Cmp::Eq => left == right,
+        Cmp::Ne => left != right,
+        Cmp::L => left < right,
+        Cmp::Le => left <= right,
+        Cmp::G => left > right,
+        Cmp::Ge => left >= right,
+    }
+}
+
 enum ProgState {
     Halt(i32),
     Running,
+    // the program did something it can't recover from, like shifting by -1
+    Crash(ErrorType),
 }
 
 fn get_inst_size(instruction_num: u8) -> usize {
     match instruction_num {
-        RET_NUM => 3,
+        RET_NUM => 1,
         PUSH_NUM => 4,
         POP_NUM => 1,
         MOV_NUM => 3,
@@ -651,6 +801,14 @@ fn get_inst_size(instruction_num: u8) -> usize {
         ARRPUSH_NUM => 1,
         FREEARR_NUM => 1,
         ARRIND_NUM => 1,
+        SHL_NUM => 1,
+        SHR_NUM => 1,
+        POW_NUM => 1,
+        CALLNATIVE_NUM => 3,
+        CALLHOST_NUM => 3,
+        NE_NUM => 1,
+        MOVPOP_NUM => 3,
+        JMPIFFALSE_NUM => 6,
         _ => unreachable!(),
     }
 }
@@ -668,3 +826,44 @@ pub fn get_type_size(type_num: u8) -> usize {
         }
     }) + 1
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::test_util::{errors, run};
+
+    #[test]
+    fn frames_with_mixed_sizes() {
+        let code = "fun mix(int a, dcml b, bool c) -> int {
+    dcml d = b * 2.0;
+    int e = a + 1;
+    if c { return e + (d become int); }
+    return e;
+}
+fun main() -> int {
+    int x = 5;
+    bool yes = true;
+    int r = mix(x, 1.5, yes) + mix(1, 0.5, false);
+    return r + x;
+}";
+        assert_eq!(run(code), 16);
+    }
+
+    #[test]
+    fn int_power_overflow_crashes() {
+        let code = "fun raise(int a, int b) -> int { return a ** b; }
+fun main() -> int { return raise(2, 40); }";
+        assert_eq!(errors(code)[0].e_type.code(), "R0008");
+        assert_eq!(run(&code.replace("40", "30")), 1 << 30);
+    }
+
+    #[test]
+    fn bad_shift_amounts_crash() {
+        let code = "fun shift(int a, int b) -> int { return (a << b) + (a >> b); }
+fun main() -> int { return shift(1, AMOUNT); }";
+        let crash = |amount: &str| errors(&code.replace("AMOUNT", amount))[0].e_type.code();
+        assert_eq!(crash("0 - 1"), "R0001");
+        assert_eq!(crash("32"), "R0002");
+        assert_eq!(crash("40"), "R0002");
+        assert_eq!(run(&code.replace("AMOUNT", "31")), i32::MIN);
+    }
+}
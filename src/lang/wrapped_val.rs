@@ -1,6 +1,6 @@
-use super::tokens::Type;
+use super::{errors::ErrorType, tokens::Type};
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum WrappedVal {
     CallStack(u32),
     Int(i32),
@@ -21,6 +21,48 @@ impl WrappedVal {
             WrappedVal::Array(_) => Type::Array(Box::new(Type::Int)),
         }
     }
+    // shifts can fail at runtime, so they give back the error for the VM
+    pub fn checked_shl(self, rhs: Self) -> Result<Self, ErrorType> {
+        let (WrappedVal::Int(lhs), WrappedVal::Int(rhs)) = (self, rhs) else {
+            unreachable!();
+        };
+        WrappedVal::check_shift_amount(rhs)?;
+        Ok(WrappedVal::Int(lhs << rhs))
+    }
+    pub fn checked_shr(self, rhs: Self) -> Result<Self, ErrorType> {
+        let (WrappedVal::Int(lhs), WrappedVal::Int(rhs)) = (self, rhs) else {
+            unreachable!();
+        };
+        WrappedVal::check_shift_amount(rhs)?;
+        Ok(WrappedVal::Int(lhs >> rhs))
+    }
+    fn check_shift_amount(amount: i32) -> Result<(), ErrorType> {
+        if amount < 0 {
+            Err(ErrorType::NegativeShift { amount })
+        } else if amount >= i32::BITS as i32 {
+            Err(ErrorType::ShiftTooBig { amount })
+        } else {
+            Ok(())
+        }
+    }
+    pub fn checked_pow(self, rhs: Self) -> Result<Self, ErrorType> {
+        match (self, rhs) {
+            (WrappedVal::Int(lhs), WrappedVal::Int(rhs)) => {
+                if rhs < 0 {
+                    return Err(ErrorType::NegativePower { exp: rhs });
+                }
+                match lhs.checked_pow(rhs as u32) {
+                    Some(ans) => Ok(WrappedVal::Int(ans)),
+                    None => Err(ErrorType::PowerOverflow {
+                        base: lhs,
+                        exp: rhs,
+                    }),
+                }
+            }
+            (WrappedVal::Dcml(lhs), WrappedVal::Dcml(rhs)) => Ok(WrappedVal::Dcml(lhs.powf(rhs))),
+            _ => unreachable!(),
+        }
+    }
 }
 impl std::ops::Add for WrappedVal {
     type Output = Self;
@@ -154,6 +196,16 @@ impl std::ops::BitOr for WrappedVal {
         }
     }
 }
+impl std::ops::Not for WrappedVal {
+    type Output = Self;
+    fn not(self) -> Self::Output {
+        match self {
+            WrappedVal::Bool(val) => WrappedVal::Bool(!val),
+            WrappedVal::Int(val) => WrappedVal::Int(!val),
+            _ => unreachable!(),
+        }
+    }
+}
 impl std::ops::BitXor for WrappedVal {
     type Output = Self;
     fn bitxor(self, rhs: Self) -> Self::Output {
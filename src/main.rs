@@ -1,9 +1,24 @@
-use std::env;
+use std::{env, fs};
+
+use lang::{
+    count_instructions,
+    errors::render_errors,
+    explain,
+    keywords::{ENGLISH, KEYWORD_SETS, keyword_set, translate},
+};
 
 mod gui;
 mod lang;
 mod tester;
 
+// the same kind of code levels/fib.json checks, it makes lots of calls
+const FIB_BENCH: &str = "fun test(int n) -> int {
+    if n < 2 { return n; }
+    return test(n - 1) + test(n - 2);
+}
+fun main() -> int { return test(20); }
+";
+
 fn main() -> Result<(), slint::PlatformError> {
     // println!("Hello, world!");
     // let args: Vec<String> = env::args().collect();
@@ -18,5 +33,76 @@ fn main() -> Result<(), slint::PlatformError> {
     //         x => println!("Invalid argument: {}", x),
     //     }
     // }
-    crate::gui::run_gui_test(Vec::new())
+    // messages are in the language from PRISM_LANG (like "es"), or the system's
+    if let Ok(lang) = env::var("PRISM_LANG").or_else(|_| env::var("LANG")) {
+        explain::set_locale(&lang);
+    }
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 3 && args[1] == "explain" {
+        match explain::explain(&args[2]) {
+            Some(text) => println!("{}", text),
+            None => println!(
+                "{}",
+                explain::fill(
+                    explain::message_template("no_such_code"),
+                    &[("code", args[2].to_owned())]
+                )
+            ),
+        }
+        return Ok(());
+    }
+    // translate <file> <from> <to>, like `translate game.pi es en`
+    if args.len() == 5 && args[1] == "translate" {
+        let code = fs::read_to_string(&args[2]).expect("File not found");
+        let (Some(from), Some(to)) = (keyword_set(&args[3]), keyword_set(&args[4])) else {
+            let names = KEYWORD_SETS.map(|set| set.name).join(", ");
+            println!(
+                "{}",
+                explain::fill(
+                    explain::message_template("keyword_sets"),
+                    &[("names", names)]
+                )
+            );
+            return Ok(());
+        };
+        match translate(&code, from, to) {
+            Ok(translated) => print!("{}", translated),
+            Err(errors) => println!("{}", render_errors(&errors, &code)),
+        }
+        return Ok(());
+    }
+    // bench [file], how many instructions run with and without the peephole pass
+    if (args.len() == 2 || args.len() == 3) && args[1] == "bench" {
+        let code = match args.get(2) {
+            Some(path) => fs::read_to_string(path).expect("File not found"),
+            None => FIB_BENCH.to_owned(),
+        };
+        let results = count_instructions(code.to_owned(), &ENGLISH, false)
+            .and_then(|naive| Ok((naive, count_instructions(code.to_owned(), &ENGLISH, true)?)));
+        match results {
+            Ok(((val, naive), (_, optimized))) => {
+                println!("The end value was {}", val);
+                println!("Without peephole: {} instructions", naive);
+                println!(
+                    "With peephole:    {} instructions ({:.1}% fewer)",
+                    optimized,
+                    100.0 * (naive - optimized) as f64 / naive as f64
+                );
+            }
+            Err(errors) => println!("{}", render_errors(&errors, &code)),
+        }
+        return Ok(());
+    }
+    // lang <debug|eval> <file> [opt], shows the instructions for a file and runs it,
+    // `opt` shows the instructions from before optimizing too
+    if (args.len() == 4 || args.len() == 5) && args[1] == "lang" {
+        crate::lang::run_lang_test(args);
+        return Ok(());
+    }
+    // code in the gui is written with the keywords from PRISM_KEYWORDS (like "es")
+    let keywords = env::var("PRISM_KEYWORDS")
+        .ok()
+        .and_then(|name| keyword_set(&name))
+        .unwrap_or(&ENGLISH);
+    crate::gui::run_gui_test(Vec::new(), keywords)
 }
@@ -1,7 +1,9 @@
 use std::{fs, time::Duration};
 
 use crate::lang::{
-    errors::CompileError,
+    DEFAULT_SEED, call_graph,
+    errors::{CompileError, ErrorType},
+    keywords::{ENGLISH, KeywordSet, translate},
     run_code_timed,
     tokens::{Literal, Type},
 };
@@ -15,6 +17,9 @@ pub struct TestResult {
     pub errors: Vec<CompileError>,
     pub correct: u32,
     pub incorrect: u32,
+    // the code the errors were found in, with the level's wrappers on the end,
+    // so they can be rendered against the same lines they point at
+    pub code: String,
 }
 
 pub struct TestInfo {
@@ -22,20 +27,33 @@ pub struct TestInfo {
     pub inputs_type: Vec<Type>,
     pub output_type: Type,
     pub json: JsonValue,
+    // the words the code is written with
+    pub keywords: &'static KeywordSet,
 }
 
 include!(concat!(env!("OUT_DIR"), "/jsonstuff.rs"));
 
+/// English code, rewritten with the words in `keywords`, so the code wrapped
+/// around a solution matches the language it is written in.
+pub fn in_keywords(code: &str, keywords: &'static KeywordSet) -> String {
+    // the wrappers only use names that aren't a keyword in any set
+    translate(code, &ENGLISH, keywords).expect("wrapper code clashes with a keyword")
+}
+
 /// This function assumes an entry point of 'fun test(inputs) -> output'.
 /// The json also needs to be formatted in a specific way. There must be a top
 /// level value "tests" that holds an array of objects. These objects must have a
 /// value "inputs" which holds an array of data, and a value "output" wich holds one
-/// piece of data.
+/// piece of data. If it has `"requires_recursion": true`, `test` also has to end up
+/// calling itself somewhere, or none of the tests count. A "seed" picks the numbers
+/// `random()` gives, otherwise every level uses the same default one.
 pub fn test_against_json(data: TestInfo) -> TestResult {
+    let seed = data.json["seed"].as_u64().unwrap_or(DEFAULT_SEED);
     let tests = data.json["tests"].clone();
     let length: usize = tests[0].dump().parse().unwrap();
     let mut correct = 0;
     let mut errors = Vec::new();
+    let mut errors_code = data.code.clone();
     for i in 1..length {
         let length: usize = tests[i]["inputs"][0].dump().parse().unwrap();
         let mut inputs_string = String::new();
@@ -51,24 +69,43 @@ pub fn test_against_json(data: TestInfo) -> TestResult {
             "fun main() -> int {{ if test({}) == {} {{ return 0; }} return 1; }}",
             inputs_string, output
         );
-        let new_code = data.code.clone() + code_to_add.as_str();
+        let new_code = data.code.clone() + &in_keywords(&code_to_add, data.keywords);
         // println!("{}", new_code);
-        let res = run_code_timed(new_code, Duration::from_secs(2), Duration::from_millis(100));
+        let res = run_code_timed(
+            new_code.clone(),
+            data.keywords,
+            seed,
+            Duration::from_secs(2),
+            Duration::from_millis(100),
+        );
         match res {
-            Ok(Some(x)) if x == 0 => {
+            Ok((Some(x), _)) if x == 0 => {
                 correct += 1;
             }
             Err(e) if errors.len() == 0 => {
                 errors.extend(e);
+                errors_code = new_code;
             }
             _ => (),
         }
     }
+    let mut success = correct == length - 1;
+    if data.json["requires_recursion"].as_bool() == Some(true)
+        && let Ok(graph) = call_graph(data.code.clone(), data.keywords)
+        && !graph
+            .reachable_from("test")
+            .iter()
+            .any(|name| graph.is_recursive(name))
+    {
+        errors.push(CompileError::new(ErrorType::NeedsRecursion, 0, 0));
+        success = false;
+    }
     return TestResult {
-        success: correct == length - 1,
+        success,
         errors,
         correct: correct as u32,
         incorrect: (length - 1 - correct) as u32,
+        code: errors_code,
     };
 }
 
@@ -93,3 +130,91 @@ pub fn test_against_json(data: TestInfo) -> TestResult {
 }
 
 */
+
+#[cfg(test)]
+mod tests {
+    use super::{LEVELS, TestInfo, in_keywords, test_against_json};
+    use crate::lang::{
+        errors::render_errors,
+        keywords::{ENGLISH, SPANISH},
+        tokens::Type,
+    };
+
+    #[test]
+    fn spanish_solution_passes() {
+        let code = "funcion fib(entero n) -> entero {
+    si n < 2 { devolver n; }
+    devolver fib(n - 1) + fib(n - 2);
+}";
+        let wrapper = " fun test(int num) -> int { return fib(num); }";
+        let result = test_against_json(TestInfo {
+            code: code.to_owned() + &in_keywords(wrapper, &SPANISH),
+            inputs_type: vec![Type::Int],
+            output_type: Type::Int,
+            json: json::parse(LEVELS[2]).unwrap(),
+            keywords: &SPANISH,
+        });
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert!(result.success);
+    }
+
+    #[test]
+    fn wrapper_errors_render_against_the_wrapper() {
+        // fib takes the wrong type, so the call in the wrapper is the error
+        let code = "fun fib(dcml n) -> int {
+    return 0;
+}";
+        let result = test_against_json(TestInfo {
+            code: code.to_owned() + " fun test(int num) -> int { return fib(num); }",
+            inputs_type: vec![Type::Int],
+            output_type: Type::Int,
+            json: json::parse(LEVELS[2]).unwrap(),
+            keywords: &ENGLISH,
+        });
+        let rendered = render_errors(&result.errors, &result.code);
+        let underlined = rendered.lines().nth(2).unwrap();
+        let carets = underlined.find('^').unwrap();
+        let src_line = rendered.lines().nth(1).unwrap();
+        assert!(src_line[carets..].starts_with("fib(num)"), "{}", rendered);
+    }
+
+    #[test]
+    fn loops_fail_a_level_that_requires_recursion() {
+        let mut json = json::parse(LEVELS[2]).unwrap();
+        json["requires_recursion"] = true.into();
+        let looping = "fun fib(int n) -> int {
+    int a = 0;
+    int b = 1;
+    while n > 0 {
+        int next = a + b;
+        a = b;
+        b = next;
+        n = n - 1;
+    }
+    return a;
+}";
+        let recursive = "fun fib(int n) -> int {
+    if n < 2 { return n; }
+    return fib(n - 1) + fib(n - 2);
+}";
+        let wrapper = " fun test(int num) -> int { return fib(num); }";
+        let run = |code: &str| {
+            test_against_json(TestInfo {
+                code: code.to_owned() + wrapper,
+                inputs_type: vec![Type::Int],
+                output_type: Type::Int,
+                json: json.clone(),
+                keywords: &ENGLISH,
+            })
+        };
+        let result = run(looping);
+        // every answer is right, it just doesn't recurse
+        assert_eq!(result.incorrect, 0);
+        assert!(!result.success);
+        let codes: Vec<_> = result.errors.iter().map(|e| e.e_type.code()).collect();
+        assert_eq!(codes, vec!["C0002"]);
+        let result = run(recursive);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert!(result.success);
+    }
+}